@@ -0,0 +1,291 @@
+use std::cell::{Ref, RefCell};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::computer::{CPUState, Tape, Word};
+use crate::peripheral::{Bus, Joystick, Screen};
+
+/// The tiles drawn on day 13
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl Tile {
+    pub fn from_word(word: Word) -> Option<Tile> {
+        match word {
+            0 => Some(Tile::Empty),
+            1 => Some(Tile::Wall),
+            2 => Some(Tile::Block),
+            3 => Some(Tile::Paddle),
+            4 => Some(Tile::Ball),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '=',
+            Tile::Paddle => '_',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+/// Draws a screen as text, with `?` for unknown tiles
+pub fn render(screen: &Screen) -> String {
+    screen.tiles.render(|tile| match tile {
+        Some(word) => Tile::from_word(*word).map_or('?', Tile::symbol),
+        None => ' ',
+    })
+}
+
+/// Chooses how to move the joystick, given what's on the screen: -1 for
+/// left, 0 to stay still and 1 for right
+pub trait Strategy {
+    fn tilt(&mut self, screen: &Screen) -> Word;
+}
+
+/// Keeps the paddle under the ball
+pub struct Autoplayer;
+
+impl Strategy for Autoplayer {
+    fn tilt(&mut self, screen: &Screen) -> Word {
+        match (screen.find(Tile::Ball as Word), screen.find(Tile::Paddle as Word)) {
+            (Some(ball), Some(paddle)) => (ball.x - paddle.x).signum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Lets someone play on a terminal. Before each move the screen and
+/// score are shown, then a line starting `a` moves left, `d` moves right
+/// and anything else stays still. Once the input runs out the joystick is
+/// left in the middle.
+pub struct Human<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl Human<io::StdinLock<'static>, io::Stdout> {
+    pub fn stdio() -> Self {
+        Human::new(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Human<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Human { reader, writer }
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    fn ask(&mut self, screen: &Screen) -> io::Result<Word> {
+        write!(self.writer, "{}Score: {}\nleft (a), stay (s), right (d)> ", render(screen), screen.score)?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        Ok(match line.trim().chars().next() {
+            Some('a') => -1,
+            Some('d') => 1,
+            _ => 0,
+        })
+    }
+}
+
+impl<R: BufRead, W: Write> Strategy for Human<R, W> {
+    fn tilt(&mut self, screen: &Screen) -> Word {
+        self.ask(screen).unwrap_or(0)
+    }
+}
+
+/// An arcade cabinet running a game like the one on day 13, which draws
+/// with `x, y, tile` triples and shows its score with `-1, 0, score`
+///
+/// ```
+/// use common::arcade::{Cabinet, Tile};
+///
+/// let mut cabinet = Cabinet::new(&"104,1,104,2,104,2,104,-1,104,0,104,7,99".parse().unwrap());
+/// cabinet.run();
+/// assert_eq!(1, cabinet.count(Tile::Block));
+/// assert_eq!(7, cabinet.score());
+/// ```
+pub struct Cabinet {
+    pub bus: Bus,
+    screen: Rc<RefCell<Screen>>,
+    joystick: Option<Rc<RefCell<Joystick>>>,
+}
+
+impl Cabinet {
+    /// A cabinet with no joystick, which can only run the attract mode
+    pub fn new(tape: &Tape) -> Cabinet {
+        let screen = Rc::new(RefCell::new(Screen::default()));
+        let mut bus = Bus::new(tape);
+        bus.attach(Rc::clone(&screen));
+        Cabinet { bus, screen, joystick: None }
+    }
+
+    /// A cabinet set for free play, with a joystick moved by the strategy
+    pub fn free_play<S: Strategy + 'static>(tape: &Tape, strategy: S) -> Cabinet {
+        let mut cabinet = Cabinet::with_strategy(tape, strategy);
+        cabinet.insert_quarters(2);
+        cabinet
+    }
+
+    /// A cabinet with a joystick moved by the strategy, but no quarters
+    /// inserted, for games which don't keep count of them
+    pub fn with_strategy<S: Strategy + 'static>(tape: &Tape, mut strategy: S) -> Cabinet {
+        let mut cabinet = Cabinet::new(tape);
+        let screen = Rc::clone(&cabinet.screen);
+        let joystick = Rc::new(RefCell::new(Joystick::new(move || strategy.tilt(&screen.borrow()))));
+        cabinet.bus.attach(Rc::clone(&joystick));
+        cabinet.joystick = Some(joystick);
+        cabinet
+    }
+
+    /// Sets the number of quarters inserted, which the game keeps at
+    /// address 0. 2 means free play.
+    pub fn insert_quarters(&mut self, quarters: Word) {
+        self.bus.computer.memory.write_direct(0, quarters);
+    }
+
+    /// Plays until the game ends, or until it wants the joystick when
+    /// there isn't one
+    pub fn run(&mut self) -> CPUState {
+        self.bus.run()
+    }
+
+    pub fn screen(&self) -> Ref<'_, Screen> {
+        self.screen.borrow()
+    }
+
+    pub fn score(&self) -> Word {
+        self.screen().score
+    }
+
+    /// How many of a tile are on the screen
+    pub fn count(&self, tile: Tile) -> usize {
+        self.screen().count(tile as Word)
+    }
+
+    /// How many times the joystick has been moved
+    pub fn moves(&self) -> usize {
+        self.joystick.as_ref().map_or(0, |joystick| joystick.borrow().reads)
+    }
+
+    pub fn render(&self) -> String {
+        render(&self.screen())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    /// A tiny game where the ball runs along a row, bouncing off the
+    /// ends. Each time the joystick is read the paddle moves, and it has
+    /// to be under the ball or the game is over. Every hit scores a point
+    /// and knocks out the block above.
+    fn game(turns: Word) -> Tape {
+        compile(&format!("
+            fn draw(x, y, tile) {{ output(x); output(y); output(tile); }}
+            fn main() {{
+                let x = 0;
+                while x < 7 {{ draw(x, 0, 1); x = x + 1; }}
+                x = 1;
+                while x < 6 {{ draw(x, 1, 2); x = x + 1; }}
+                let paddle = 2;
+                let ball = 1;
+                let dx = 1;
+                let score = 0;
+                draw(paddle, 4, 3);
+                draw(ball, 3, 4);
+                draw(-1, 0, score);
+
+                let turn = 0;
+                while turn < {} {{
+                    let tilt = input();
+                    draw(paddle, 4, 0);
+                    paddle = paddle + tilt;
+                    draw(paddle, 4, 3);
+                    if paddle != ball {{ return; }}
+
+                    score = score + 1;
+                    draw(-1, 0, score);
+                    draw(ball, 1, 0);
+                    draw(ball, 3, 0);
+                    if ball + dx < 1 || ball + dx > 5 {{ dx = -dx; }}
+                    ball = ball + dx;
+                    draw(ball, 3, 4);
+                    turn = turn + 1;
+                }}
+            }}", turns)).unwrap()
+    }
+
+    struct Still;
+
+    impl Strategy for Still {
+        fn tilt(&mut self, _: &Screen) -> Word {
+            0
+        }
+    }
+
+    #[test]
+    fn test_attract_mode() {
+        let mut cabinet = Cabinet::new(&game(10));
+        assert_eq!(CPUState::AwaitingInput, cabinet.run());
+        assert_eq!(5, cabinet.count(Tile::Block));
+        assert_eq!("#######\n ===== \n       \n o     \n  _    \n", cabinet.render());
+    }
+
+    #[test]
+    fn test_autoplayer() {
+        let mut cabinet = Cabinet::with_strategy(&game(6), Autoplayer);
+        assert_eq!(CPUState::Halted, cabinet.run());
+        assert_eq!(6, cabinet.score());
+        assert_eq!(6, cabinet.moves());
+        assert_eq!(0, cabinet.count(Tile::Block));
+
+        let mut cabinet = Cabinet::with_strategy(&game(6), Still);
+        assert_eq!(CPUState::Halted, cabinet.run());
+        assert_eq!(0, cabinet.score());
+        assert_eq!(1, cabinet.moves());
+    }
+
+    #[test]
+    fn test_quarters() {
+        // Like day 13, the word at address 0 is both the number of quarters
+        // and an instruction, here adding or multiplying the score
+        let tape = "1,11,11,12,104,-1,104,0,4,12,99,3,0".parse().unwrap();
+        let mut cabinet = Cabinet::new(&tape);
+        cabinet.run();
+        assert_eq!(6, cabinet.score());
+
+        let mut cabinet = Cabinet::free_play(&tape, Still);
+        cabinet.run();
+        assert_eq!(9, cabinet.score());
+    }
+
+    #[test]
+    fn test_human() {
+        let mut human = Human::new("a\nd\n\n".as_bytes(), Vec::new());
+        let mut screen = Screen::default();
+        screen.tiles.insert(crate::grid::Point::new(0, 0), Tile::Ball as Word);
+        screen.score = 3;
+
+        let tilts: Vec<Word> = (0 .. 4).map(|_| human.tilt(&screen)).collect();
+        assert_eq!(vec![-1, 1, 0, 0], tilts);
+        let shown = String::from_utf8(human.writer().clone()).unwrap();
+        assert!(shown.starts_with("o\nScore: 3\nleft (a), stay (s), right (d)> "));
+    }
+}
@@ -0,0 +1,212 @@
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+
+use crate::computer::{Computer, CPUState, Tape, Word};
+
+/// Sends input to an `AsyncComputer`
+pub type Input = UnboundedSender<Word>;
+/// The output of an `AsyncComputer`, as a `Stream`
+pub type Output = UnboundedReceiver<Word>;
+
+/// A computer which runs as a future. When the program waits for input
+/// it yields to the executor until some arrives, and everything it
+/// outputs is sent straight on, so many computers can talk to each
+/// other on a single thread.
+///
+/// The future finishes when the program halts or faults, or if it waits
+/// for input after every `Input` has been dropped, and gives back the
+/// computer so its memory and state can be inspected.
+///
+/// ```
+/// use futures::executor::block_on;
+/// use futures::StreamExt;
+/// use common::async_computer::AsyncComputer;
+/// use common::computer::CPUState;
+///
+/// let (computer, input, mut output) = AsyncComputer::new(&"3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap());
+/// input.unbounded_send(8).unwrap();
+///
+/// let computer = block_on(computer.run());
+/// assert_eq!(CPUState::Halted, computer.cpu_state());
+/// assert_eq!(Some(1), block_on(output.next()));
+/// ```
+pub struct AsyncComputer {
+    computer: Computer,
+    input: UnboundedReceiver<Word>,
+    output: UnboundedSender<Word>,
+    /// The number of instructions run before giving other futures a turn
+    pub slice: NonZeroUsize,
+}
+
+impl AsyncComputer {
+    /// Creates a computer running the tape, along with its input and output
+    pub fn new(tape: &Tape) -> (AsyncComputer, Input, Output) {
+        AsyncComputer::with_computer(Computer::new_with_tape(tape))
+    }
+
+    /// Wraps a computer which has already been set up, for example with
+    /// protection or a different instruction set. Any input already
+    /// queued is used first.
+    pub fn with_computer(computer: Computer) -> (AsyncComputer, Input, Output) {
+        let (input_sender, input) = mpsc::unbounded();
+        let (output, output_receiver) = mpsc::unbounded();
+        (AsyncComputer { computer, input, output, slice: NonZeroUsize::new(1000).unwrap() }, input_sender, output_receiver)
+    }
+
+    /// Runs the program until it halts or faults, or until it is waiting
+    /// for input which will never come
+    pub async fn run(mut self) -> Computer {
+        let mut steps = 0;
+        loop {
+            let state = self.computer.step();
+            self.send_output();
+
+            match state {
+                CPUState::AwaitingInstruction => {
+                    steps += 1;
+                    if steps % self.slice.get() == 0 {
+                        YieldNow(false).await;
+                    }
+                },
+                CPUState::AwaitingInput => match self.input.next().await {
+                    Some(value) => self.computer.io.add_input(value),
+                    None => return self.computer,
+                },
+                CPUState::Halted | CPUState::Faulted(_) => return self.computer,
+            }
+        }
+    }
+
+    fn send_output(&mut self) {
+        for value in self.computer.io.output.drain(..) {
+            // Nobody listening is fine, the output is just lost
+            let _ = self.output.unbounded_send(value);
+        }
+    }
+}
+
+/// Returns pending once so that other futures get to run
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        context.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::LocalPool;
+    use futures::task::LocalSpawnExt;
+
+    use super::*;
+
+    #[test]
+    fn test_feedback_loop() {
+        let tape: Tape = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5".parse().unwrap();
+        let mut pool = LocalPool::new();
+        let spawner = pool.spawner();
+
+        let amplifiers: Vec<(AsyncComputer, Input, Output)> = (0 .. 5).map(|_| AsyncComputer::new(&tape)).collect();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for ((amplifier, input, output), phase) in amplifiers.into_iter().zip(&[9, 8, 7, 6, 5]) {
+            input.unbounded_send(*phase).unwrap();
+            inputs.push(input);
+            outputs.push(output);
+            spawner.spawn_local(async move { amplifier.run().await; }).unwrap();
+        }
+        inputs[0].unbounded_send(0).unwrap();
+
+        // Each amplifier feeds the next, and the last feeds the first
+        // as well as being recorded
+        let (last, recorded) = mpsc::unbounded();
+        for (i, mut output) in outputs.into_iter().enumerate() {
+            let input = inputs[(i + 1) % 5].clone();
+            let last = if i == 4 { Some(last.clone()) } else { None };
+            spawner.spawn_local(async move {
+                while let Some(value) = output.next().await {
+                    let _ = input.unbounded_send(value);
+                    if let Some(last) = last.as_ref() {
+                        last.unbounded_send(value).unwrap();
+                    }
+                }
+            }).unwrap();
+        }
+        drop(last);
+        drop(inputs);
+
+        pool.run();
+        let signals: Vec<Word> = pool.run_until(recorded.collect());
+        assert_eq!(Some(&139629729), signals.last());
+    }
+
+    #[test]
+    fn test_many_computers() {
+        let mut pool = LocalPool::new();
+        let spawner = pool.spawner();
+
+        // A chain of computers each adding one to what they're given
+        let tape: Tape = "3,0,1001,0,1,0,4,0,99".parse().unwrap();
+        let (first, mut previous) = mpsc::unbounded();
+        for _ in 0 .. 500 {
+            let (computer, input, output) = AsyncComputer::new(&tape);
+            spawner.spawn_local(async move { let _ = previous.map(Ok).forward(input).await; }).unwrap();
+            spawner.spawn_local(async move { computer.run().await; }).unwrap();
+            previous = output;
+        }
+
+        first.unbounded_send(0).unwrap();
+        let result = pool.run_until(previous.next());
+        assert_eq!(Some(500), result);
+    }
+
+    #[test]
+    fn test_yields_while_running() {
+        let mut pool = LocalPool::new();
+        let spawner = pool.spawner();
+
+        // Counts down from 5000 without ever waiting for input
+        let (mut busy, _, _) = AsyncComputer::new(&"1001,10,-1,10,1005,10,0,104,1,99,5000".parse().unwrap());
+        busy.slice = NonZeroUsize::new(10).unwrap();
+        let (echo, input, mut output) = AsyncComputer::new(&"3,0,4,0,99".parse().unwrap());
+        input.unbounded_send(7).unwrap();
+
+        let order = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = order.clone();
+        spawner.spawn_local(async move {
+            busy.run().await;
+            log.borrow_mut().push("busy");
+        }).unwrap();
+        let log = order.clone();
+        spawner.spawn_local(async move {
+            echo.run().await;
+            log.borrow_mut().push("echo");
+        }).unwrap();
+
+        pool.run();
+        assert_eq!(vec!["echo", "busy"], *order.borrow());
+        assert_eq!(Some(7), pool.run_until(output.next()));
+    }
+
+    #[test]
+    fn test_closed_input() {
+        let (computer, input, _) = AsyncComputer::new(&"3,0,99".parse().unwrap());
+        drop(input);
+
+        let computer = futures::executor::block_on(computer.run());
+        assert_eq!(CPUState::AwaitingInput, computer.cpu_state());
+    }
+}
@@ -0,0 +1,277 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use common::arcade::{Autoplayer, Cabinet, Human};
+use common::binary;
+use common::computer::{Computer, CPUState, Reference, Tape, Word};
+use common::console::Console;
+use common::decompiler::decompile;
+use common::differential::Limits;
+use common::dump::{describe_diff, Dump, Viewer};
+use common::equivalence::{self, Domain};
+use common::visualizer::{self, Visualizer};
+
+const USAGE: &str = "\
+Usage:
+  intcode dump TAPE [options]    Run a tape and print its memory
+      --input 1,2,3              Input to give the program
+      --steps N                  Stop after N instructions
+      --snapshot N               Mark words changed since instruction N
+      --from ADDRESS --to ADDRESS
+                                 Only show part of memory
+      --width N                  Words per row, 8 by default
+      --save FILE                Also save the dump to a file
+  intcode diff BEFORE AFTER      Compare two saved dumps
+  intcode decompile TAPE         Print a tape as pseudocode
+  intcode convert FROM TO        Convert a tape file to a binary tape, or a
+                                 binary tape back to text
+  intcode equiv LEFT RIGHT [options]
+                                 Check two tapes behave the same
+      --range A..B               Try every value in a range as the next
+                                 input, can be repeated
+      --input 1,2,3              Try this input, can be repeated
+      --steps N                  Give up on runs after N instructions
+      --memory N                 Fault on addresses from N
+  intcode play TAPE              Run a tape, asking for input as needed
+  intcode arcade TAPE [--auto]   Play an arcade game, or watch it play itself
+  intcode tui TAPE [--input 1,2,3]
+                                 Watch a tape run in the terminal";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("dump") => dump(&args[1 ..]),
+        Some("diff") => diff(&args[1 ..]),
+        Some("decompile") => decompile_tape(&args[1 ..]),
+        Some("convert") => convert(&args[1 ..]),
+        Some("equiv") => equiv(&args[1 ..]),
+        Some("play") => play(&args[1 ..]),
+        Some("arcade") => arcade(&args[1 ..]),
+        Some("tui") => tui(&args[1 ..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path, e))
+}
+
+/// Loads a tape file or binary tape
+fn load(path: &str) -> Result<Tape, String> {
+    Tape::load(path).map_err(|e| format!("Couldn't load {}: {}", path, e))
+}
+
+fn number<T: std::str::FromStr>(value: Option<&String>, option: &str) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or(format!("{} needs a number", option))
+}
+
+fn dump(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let tape = load(path)?;
+
+    let mut input: Vec<Word> = Vec::new();
+    let mut steps = None;
+    let mut snapshot_at = None;
+    let mut from = 0;
+    let mut to = None;
+    let mut width = 8;
+    let mut save = None;
+
+    let mut options = args[1 ..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--input" => {
+                let values = options.next().ok_or("--input needs values")?;
+                for value in values.split(',') {
+                    input.push(number(Some(&value.trim().to_string()), "--input")?);
+                }
+            },
+            "--steps" => steps = Some(number::<u64>(options.next(), option)?),
+            "--snapshot" => snapshot_at = Some(number::<u64>(options.next(), option)?),
+            "--from" => from = number::<Reference>(options.next(), option)?,
+            "--to" => to = Some(number::<Reference>(options.next(), option)?),
+            "--width" => width = number::<usize>(options.next(), option)?,
+            "--save" => save = Some(options.next().ok_or("--save needs a file")?.clone()),
+            _ => return Err(format!("Unknown option {}\n{}", option, USAGE)),
+        }
+    }
+
+    if from < 0 {
+        return Err("--from needs an address of 0 or more".to_string());
+    }
+
+    let mut computer = Computer::new_with_tape(&tape);
+    for value in input {
+        computer.io.add_input(value);
+    }
+
+    let range = |computer: &Computer| from .. to.unwrap_or(computer.memory.len() as Reference).max(from);
+    let mut snapshot = None;
+    loop {
+        if Some(computer.steps()) == snapshot_at {
+            snapshot = Some(Dump::range(&computer, range(&computer)));
+        }
+        if Some(computer.steps()) == steps || computer.step() != CPUState::AwaitingInstruction {
+            break;
+        }
+    }
+
+    let dump = Dump::range(&computer, range(&computer));
+    println!("{:?} after {} steps, ip {}, rb {}", computer.cpu_state(), computer.steps(), dump.instruction_pointer, dump.relative_base);
+    if !computer.io.output.is_empty() {
+        println!("Output: {:?}", computer.io.output);
+    }
+    print!("{}", Viewer { width, snapshot: snapshot.as_ref() }.view(&dump));
+
+    if let Some(save) = save {
+        fs::write(&save, dump.to_string()).map_err(|e| format!("Couldn't write {}: {}", save, e))?;
+    }
+    Ok(())
+}
+
+fn diff(args: &[String]) -> Result<(), String> {
+    if args.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+    let load_dump = |path: &String| -> Result<Dump, String> { read(path)?.parse().map_err(|_| format!("{} isn't a dump", path)) };
+    let (before, after) = (load_dump(&args[0])?, load_dump(&args[1])?);
+
+    let differences = describe_diff(&before, &after);
+    if differences.is_empty() {
+        println!("No differences");
+    } else {
+        print!("{}", differences);
+    }
+    Ok(())
+}
+
+fn decompile_tape(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let tape = load(path)?;
+    print!("{}", decompile(&tape.contents));
+    Ok(())
+}
+
+fn convert(args: &[String]) -> Result<(), String> {
+    let (from, to) = match args {
+        [from, to] => (from, to),
+        _ => return Err(USAGE.to_string()),
+    };
+    let is_binary = binary::is_binary_file(from).map_err(|e| format!("Couldn't read {}: {}", from, e))?;
+    let tape = load(from)?;
+
+    let written = if is_binary {
+        let words: Vec<String> = tape.contents.iter().map(|w| w.to_string()).collect();
+        fs::write(to, words.join(",") + "\n")
+    } else {
+        binary::save(to, &tape)
+    };
+    written.map_err(|e| format!("Couldn't write {}: {}", to, e))
+}
+
+fn equiv(args: &[String]) -> Result<(), String> {
+    let (left, right) = match args {
+        [left, right, ..] => (left, right),
+        _ => return Err(USAGE.to_string()),
+    };
+    let left = load(left)?;
+    let right = load(right)?;
+
+    let mut ranges = Vec::new();
+    let mut inputs = Vec::new();
+    let mut limits = Limits { max_steps: 1_000_000, memory_limit: 1 << 20 };
+    let mut options = args[2 ..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--range" => {
+                let range = options.next().ok_or("--range needs a range")?;
+                let mut ends = range.splitn(2, "..");
+                let start = number(ends.next().map(|s| s.to_string()).as_ref(), "--range")?;
+                let end = number(ends.next().map(|s| s.to_string()).as_ref(), "--range")?;
+                ranges.push(start .. end);
+            },
+            "--input" => {
+                let values = options.next().ok_or("--input needs values")?;
+                let mut input = Vec::new();
+                for value in values.split(',').filter(|v| !v.trim().is_empty()) {
+                    input.push(number(Some(&value.trim().to_string()), option)?);
+                }
+                inputs.push(input);
+            },
+            "--steps" => limits.max_steps = number(options.next(), option)?,
+            "--memory" => limits.memory_limit = number(options.next(), option)?,
+            _ => return Err(format!("Unknown option {}\n{}", option, USAGE)),
+        }
+    }
+
+    let domain = match (ranges.is_empty(), inputs.is_empty()) {
+        (_, true) => Domain::Ranges(ranges),
+        (true, false) => Domain::Inputs(inputs),
+        (false, false) => return Err("Use either --range or --input".to_string()),
+    };
+    if domain.checked_len().is_none() {
+        return Err("Too many inputs to check".to_string());
+    }
+    let report = equivalence::check(&left, &right, &domain, limits);
+    println!("{}", report);
+    if report.equivalent() {
+        Ok(())
+    } else {
+        Err("The tapes aren't equivalent".to_string())
+    }
+}
+
+fn play(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let tape = load(path)?;
+
+    let mut computer = Computer::new_with_tape(&tape);
+    let state = Console::stdio().run(&mut computer).map_err(|e| format!("Terminal error: {}", e))?;
+    match state {
+        CPUState::Faulted(fault) => Err(format!("Faulted: {:?}", fault)),
+        _ => Ok(()),
+    }
+}
+
+fn arcade(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let tape = load(path)?;
+
+    let mut cabinet = match args.get(1).map(|s| s.as_str()) {
+        None => Cabinet::free_play(&tape, Human::stdio()),
+        Some("--auto") => Cabinet::free_play(&tape, Autoplayer),
+        _ => return Err(USAGE.to_string()),
+    };
+    let state = cabinet.run();
+    print!("{}", cabinet.render());
+    println!("Game over after {} moves. Score: {}", cabinet.moves(), cabinet.score());
+    match state {
+        CPUState::Faulted(fault) => Err(format!("Faulted: {:?}", fault)),
+        _ => Ok(()),
+    }
+}
+
+fn tui(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let tape = load(path)?;
+
+    let mut input = Vec::new();
+    match (args.get(1).map(|s| s.as_str()), args.get(2)) {
+        (None, _) => {},
+        (Some("--input"), Some(values)) => {
+            for value in values.split(',') {
+                input.push(number(Some(&value.trim().to_string()), "--input")?);
+            }
+        },
+        _ => return Err(USAGE.to_string()),
+    }
+
+    let mut visualizer = Visualizer::new(&tape, &input);
+    visualizer::run(&mut visualizer).map_err(|e| format!("Terminal error: {}", e))
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::computer::{Tape, Word};
+use crate::tape_file::TapeError;
+
+/// The first bytes of every binary tape
+pub const MAGIC: &[u8; 4] = b"ICT\x01";
+
+/// The version of the layout written by `encode`
+pub const VERSION: u8 = 1;
+
+/// Why bytes couldn't be decoded as a binary tape
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum DecodeError {
+    /// The bytes don't start with `MAGIC`
+    Magic,
+    Version(u8),
+    /// The bytes ended part way through
+    Truncated,
+    /// A word needs more than 64 bits
+    Overflow,
+    /// The checksum stored in the tape doesn't match its contents
+    Checksum { stored: u32, actual: u32 },
+    /// There are bytes after the checksum
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Magic => write!(f, "not a binary tape"),
+            DecodeError::Version(version) => write!(f, "unsupported binary tape version {}", version),
+            DecodeError::Truncated => write!(f, "binary tape is truncated"),
+            DecodeError::Overflow => write!(f, "binary tape has a word too large for 64 bits"),
+            DecodeError::Checksum { stored, actual } =>
+                write!(f, "checksum is {:08x} but the tape's contents give {:08x}", stored, actual),
+            DecodeError::TrailingBytes => write!(f, "binary tape has bytes after its checksum"),
+        }
+    }
+}
+
+/// 32 bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x0100_0193))
+}
+
+fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn zigzag(word: Word) -> u64 {
+    ((word << 1) ^ (word >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> Word {
+    (value >> 1) as Word ^ -((value & 1) as Word)
+}
+
+/// Reads varints from the front of a slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0 .. 64).step_by(7) {
+            let (byte, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+            self.bytes = rest;
+            let bits = (*byte & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(DecodeError::Overflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Overflow)
+    }
+}
+
+/// Encodes a tape in the binary format. This is `MAGIC`, a version byte
+/// and the number of words as a varint, then each word as a zigzag
+/// encoded varint so that small negative numbers stay small. Last comes a
+/// little endian FNV-1a checksum of everything before it.
+///
+/// ```
+/// use common::binary;
+/// use common::computer::Tape;
+///
+/// let tape: Tape = "1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap();
+/// let bytes = binary::encode(&tape);
+/// assert_eq!(23, bytes.len());
+/// assert_eq!(tape.contents, binary::decode(&bytes).unwrap().contents);
+/// ```
+pub fn encode(tape: &Tape) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    put_varint(&mut bytes, tape.contents.len() as u64);
+    for word in &tape.contents {
+        put_varint(&mut bytes, zigzag(*word));
+    }
+    let sum = checksum(&bytes);
+    bytes.extend_from_slice(&sum.to_le_bytes());
+    bytes
+}
+
+/// Decodes a tape written by `encode`
+pub fn decode(bytes: &[u8]) -> Result<Tape, DecodeError> {
+    if !is_binary(bytes) {
+        return Err(DecodeError::Magic);
+    }
+    let version = *bytes.get(MAGIC.len()).ok_or(DecodeError::Truncated)?;
+    if version != VERSION {
+        return Err(DecodeError::Version(version));
+    }
+
+    let mut reader = Reader { bytes: &bytes[MAGIC.len() + 1 ..] };
+    let len = reader.varint()?;
+    // Every word takes at least a byte, which stops a corrupt length
+    // reserving huge amounts of memory
+    if len > reader.bytes.len() as u64 {
+        return Err(DecodeError::Truncated);
+    }
+    let mut contents = Vec::with_capacity(len as usize);
+    for _ in 0 .. len {
+        contents.push(unzigzag(reader.varint()?));
+    }
+
+    let end = bytes.len() - reader.bytes.len();
+    let stored = match reader.bytes {
+        [a, b, c, d] => u32::from_le_bytes([*a, *b, *c, *d]),
+        rest if rest.len() > 4 => return Err(DecodeError::TrailingBytes),
+        _ => return Err(DecodeError::Truncated),
+    };
+    let actual = checksum(&bytes[.. end]);
+    if stored != actual {
+        return Err(DecodeError::Checksum { stored, actual });
+    }
+    Ok(Tape { contents })
+}
+
+/// Whether the bytes look like a binary tape
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Whether a file starts like a binary tape, without reading the rest
+pub fn is_binary_file<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut start = [0; 4];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < start.len() {
+        match file.read(&mut start[read ..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(is_binary(&start[.. read]))
+}
+
+/// Loads a binary tape, mapping the file into memory and decoding straight
+/// from the map rather than reading it. The file mustn't be changed while
+/// it loads.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Tape, TapeError> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Err(TapeError::Binary(DecodeError::Magic));
+    }
+    // Safety: the map is only used while decoding. If another process
+    // truncates the file meanwhile, reading the missing pages kills this
+    // one with SIGBUS, and if it writes to the file the bytes can change
+    // while they're being read. Tapes are written once by `save`, so
+    // neither is expected, but nothing here prevents them.
+    let map = unsafe { Mmap::map(&file)? };
+    decode(&map).map_err(TapeError::Binary)
+}
+
+pub fn save<P: AsRef<Path>>(path: P, tape: &Tape) -> io::Result<()> {
+    fs::write(path, encode(tape))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_zigzag() {
+        let pairs = [(0, 0), (-1, 1), (1, 2), (-2, 3), (Word::MAX, u64::MAX - 1), (Word::MIN, u64::MAX)];
+        for (word, encoded) in pairs.iter() {
+            assert_eq!(*encoded, zigzag(*word));
+            assert_eq!(*word, unzigzag(*encoded));
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = encode(&Tape { contents: vec![1, -1, 300] });
+        assert_eq!(b"ICT\x01\x01\x03\x02\x01\xd8\x04", &bytes[.. 10]);
+        assert_eq!(checksum(&bytes[.. 10]).to_le_bytes(), bytes[10 ..]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = encode(&Tape { contents: vec![1, 2, 3] });
+        let changed = |i: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[i] = byte;
+            decode(&bytes).err()
+        };
+
+        assert_eq!(Some(DecodeError::Magic), decode(b"1,2,3").err());
+        assert_eq!(Some(DecodeError::Version(2)), changed(4, 2));
+        assert_eq!(Some(DecodeError::Truncated), changed(5, 9));
+        assert_eq!(Some(DecodeError::Truncated), decode(&bytes[.. bytes.len() - 1]).err());
+        assert!(matches!(changed(7, 8), Some(DecodeError::Checksum { .. })));
+        assert_eq!(Some(DecodeError::TrailingBytes), decode(&[&bytes[..], &[0]].concat()).err());
+
+        let mut overflow = MAGIC.to_vec();
+        overflow.extend_from_slice(&[VERSION, 1]);
+        overflow.extend_from_slice(&[0xff; 10]);
+        assert_eq!(Some(DecodeError::Overflow), decode(&overflow).err());
+    }
+
+    #[test]
+    fn test_load() {
+        let text = Tape::load("../day-09/input.txt").unwrap();
+        let path = std::env::temp_dir().join(format!("day-09-{}.bin", std::process::id()));
+        save(&path, &text).unwrap();
+
+        assert!(is_binary_file(&path).unwrap());
+        assert!(!is_binary_file("../day-09/input.txt").unwrap());
+        assert_eq!(text.contents, load(&path).unwrap().contents);
+        assert_eq!(text.contents, Tape::load(&path).unwrap().contents);
+        assert!(fs::metadata(&path).unwrap().len() < fs::metadata("../day-09/input.txt").unwrap().len());
+        fs::remove_file(path).unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(contents in prop::collection::vec(any::<Word>(), 0 .. 100)) {
+            let tape = Tape { contents };
+            prop_assert_eq!(tape.contents.clone(), decode(&encode(&tape)).unwrap().contents);
+        }
+    }
+}
@@ -0,0 +1,892 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::computer::{OpCode, Tape, Word};
+
+/// Operators taking one operand
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+/// Operators taking two operands. Comparisons and the logical operators
+/// give 1 for true and 0 for false, and treat any non-zero operand as
+/// true. `&&` and `||` only evaluate their right operand when needed.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Expr {
+    Number(Word),
+    Var(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A call to a function or one of the builtins `input()`, which reads
+    /// a word, and `output(value)`, which writes one and gives it back
+    Call(String, Vec<Expr>),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Statement {
+    /// Declares a variable in the enclosing block
+    Let(String, Expr),
+    Assign(String, Expr),
+    If(Expr, Vec<Statement>, Vec<Statement>),
+    While(Expr, Vec<Statement>),
+    /// Returns 0 if no value is given
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Statement>,
+}
+
+/// A parsed program, where every variable and call is known to be valid
+#[derive(PartialEq, Clone, Debug)]
+pub struct Program {
+    pub functions: Vec<Function>,
+}
+
+/// Why a program couldn't be compiled
+#[derive(PartialEq, Clone, Debug)]
+pub struct CompileError {
+    /// The line the problem was found on, from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+const BUILTINS: [(&str, usize); 2] = [("input", 0), ("output", 1)];
+
+/// Compiles a program in a small expression language to a tape.
+///
+/// A program is a list of functions, and runs by calling `main`, which
+/// takes no parameters. Functions take and return words, and can declare
+/// variables with `let`, assign them, branch with `if`/`else` and loop
+/// with `while`. Everything is an `i64`; there is no division. Comments
+/// start with `//`.
+///
+/// Variables live in stack frames addressed through the relative base,
+/// which points at the frame of the running function. A frame holds the
+/// return address, then the parameters, then the variables, then space
+/// for working out expressions. The stack starts just after the program.
+///
+/// ```
+/// use common::compiler::compile;
+/// use common::computer::Computer;
+///
+/// let tape = compile("
+///     fn factorial(n) {
+///         if n <= 1 { return 1; }
+///         return n * factorial(n - 1);
+///     }
+///
+///     fn main() {
+///         let n = input();
+///         while n > 0 {
+///             output(factorial(n));
+///             n = n - 1;
+///         }
+///     }
+/// ").unwrap();
+///
+/// let mut computer = Computer::new_with_tape(&tape);
+/// computer.io.add_input(5);
+/// computer.run();
+/// assert_eq!(vec![120, 24, 6, 2, 1], computer.io.output);
+/// ```
+pub fn compile(source: &str) -> Result<Tape, CompileError> {
+    Ok(parse(source)?.compile())
+}
+
+/// Parses a program, checking that every variable is declared before
+/// use and every call is to a function taking that many arguments
+pub fn parse(source: &str) -> Result<Program, CompileError> {
+    Parser::new(tokenize(source)?).program()
+}
+
+#[derive(PartialEq, Clone, Debug)]
+enum Token {
+    Number(Word),
+    Name(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+            Token::End => write!(f, "the end of the program"),
+        }
+    }
+}
+
+/// Longer symbols come first so `<=` isn't read as `<` then `=`
+const SYMBOLS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "+", "-", "*", "<", ">", "!", "=", "(", ")", "{", "}", ",", ";",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split("//").next().unwrap_or("");
+        let mut rest = line.trim_start();
+
+        while !rest.is_empty() {
+            let first = rest.chars().next().unwrap();
+            let length = if first.is_ascii_digit() {
+                let length = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let value = rest[.. length].parse()
+                    .map_err(|_| CompileError { line: line_number, message: format!("{} is too big", &rest[.. length]) })?;
+                tokens.push((Token::Number(value), line_number));
+                length
+            } else if first.is_alphabetic() || first == '_' {
+                let length = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+                tokens.push((Token::Name(rest[.. length].to_string()), line_number));
+                length
+            } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                tokens.push((Token::Symbol(symbol), line_number));
+                symbol.len()
+            } else {
+                return Err(CompileError { line: line_number, message: format!("unexpected {}", first) });
+            };
+            rest = rest[length ..].trim_start();
+        }
+    }
+
+    let last_line = source.lines().count().max(1);
+    tokens.push((Token::End, last_line));
+    Ok(tokens)
+}
+
+const KEYWORDS: [&str; 6] = ["fn", "let", "if", "else", "while", "return"];
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// The variables visible in each enclosing block
+    scopes: Vec<HashSet<String>>,
+    /// Every call made, with its number of arguments and line, checked
+    /// once all the functions are known
+    calls: Vec<(String, usize, usize)>,
+}
+
+impl Parser {
+    fn new(tokens: Vec<(Token, usize)>) -> Parser {
+        Parser { tokens, position: 0, scopes: Vec::new(), calls: Vec::new() }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    /// The token after the next one
+    fn peek_second(&self) -> &Token {
+        self.tokens.get(self.position + 1).map_or(&Token::End, |(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, CompileError> {
+        Err(CompileError { line: self.line(), message })
+    }
+
+    fn is(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Token::Symbol(s) if *s == symbol) || matches!(self.peek(), Token::Name(n) if n == symbol)
+    }
+
+    fn accept(&mut self, symbol: &str) -> bool {
+        if self.is(symbol) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), CompileError> {
+        if self.accept(symbol) {
+            return Ok(());
+        }
+        self.error(format!("expected {}, found {}", symbol, self.peek()))
+    }
+
+    fn name(&mut self) -> Result<String, CompileError> {
+        match self.peek().clone() {
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => {
+                self.next();
+                Ok(name)
+            },
+            other => self.error(format!("expected a name, found {}", other)),
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().insert(name.to_string());
+    }
+
+    fn program(mut self) -> Result<Program, CompileError> {
+        let mut functions: Vec<Function> = Vec::new();
+        let mut lines = HashMap::new();
+
+        while *self.peek() != Token::End {
+            let line = self.line();
+            let function = self.function()?;
+            if lines.contains_key(&function.name) || BUILTINS.iter().any(|(b, _)| *b == function.name) {
+                return Err(CompileError { line, message: format!("{} is already defined", function.name) });
+            }
+            lines.insert(function.name.clone(), line);
+            functions.push(function);
+        }
+
+        for (name, arguments, line) in &self.calls {
+            let expected = BUILTINS.iter().find(|(b, _)| b == name).map(|(_, count)| *count)
+                .or_else(|| functions.iter().find(|f| f.name == *name).map(|f| f.params.len()));
+            match expected {
+                None => return Err(CompileError { line: *line, message: format!("there's no function called {}", name) }),
+                Some(expected) if expected != *arguments => return Err(CompileError {
+                    line: *line,
+                    message: format!("{} takes {} arguments, not {}", name, expected, arguments),
+                }),
+                _ => {},
+            }
+        }
+
+        match functions.iter().find(|f| f.name == "main") {
+            None => self.error("there's no main function".to_string()),
+            Some(main) if !main.params.is_empty() => Err(CompileError { line: lines["main"], message: "main can't take parameters".to_string() }),
+            _ => Ok(Program { functions }),
+        }
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        self.expect("fn")?;
+        let name = self.name()?;
+
+        self.expect("(")?;
+        let mut params = Vec::new();
+        while !self.accept(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            let param = self.name()?;
+            if params.contains(&param) {
+                return self.error(format!("{} is already a parameter", param));
+            }
+            params.push(param);
+        }
+
+        self.scopes = vec![params.iter().cloned().collect()];
+        let body = self.block()?;
+        Ok(Function { name, params, body })
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, CompileError> {
+        self.expect("{")?;
+        self.scopes.push(HashSet::new());
+        let mut statements = Vec::new();
+        while !self.accept("}") {
+            statements.push(self.statement()?);
+        }
+        self.scopes.pop();
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, CompileError> {
+        if self.accept("let") {
+            let name = self.name()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            self.expect(";")?;
+            // Declared afterwards so `let x = x + 1` uses the outer x
+            self.declare(&name);
+            return Ok(Statement::Let(name, value));
+        }
+        if self.accept("if") {
+            return self.if_statement();
+        }
+        if self.accept("while") {
+            let condition = self.expr()?;
+            return Ok(Statement::While(condition, self.block()?));
+        }
+        if self.accept("return") {
+            let value = if self.is(";") { None } else { Some(self.expr()?) };
+            self.expect(";")?;
+            return Ok(Statement::Return(value));
+        }
+
+        let is_assignment = matches!(self.peek_second(), Token::Symbol("="));
+        if is_assignment {
+            let name = self.variable()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            self.expect(";")?;
+            return Ok(Statement::Assign(name, value));
+        }
+
+        let value = self.expr()?;
+        self.expect(";")?;
+        Ok(Statement::Expr(value))
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, CompileError> {
+        let condition = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if !self.accept("else") {
+            Vec::new()
+        } else if self.accept("if") {
+            vec![self.if_statement()?]
+        } else {
+            self.block()?
+        };
+        Ok(Statement::If(condition, then, otherwise))
+    }
+
+    /// A name which must refer to a declared variable
+    fn variable(&mut self) -> Result<String, CompileError> {
+        let line = self.line();
+        let name = self.name()?;
+        if !self.scopes.iter().any(|s| s.contains(&name)) {
+            return Err(CompileError { line, message: format!("{} isn't a variable", name) });
+        }
+        Ok(name)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    /// Parses operators from `LEVELS[level]` onwards, so lower levels bind
+    /// less tightly
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 5] = [
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual),
+                ("<", BinaryOp::Less), ("<=", BinaryOp::LessEqual),
+                (">", BinaryOp::Greater), (">=", BinaryOp::GreaterEqual),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some((_, op)) = LEVELS[level].iter().find(|(symbol, _)| self.is(symbol)) {
+            self.next();
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(*op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.accept("-") {
+            return Ok(match self.unary()? {
+                Expr::Number(n) => Expr::Number(-n),
+                operand => Expr::Unary(UnaryOp::Neg, Box::new(operand)),
+            });
+        }
+        if self.accept("!") {
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        match self.peek().clone() {
+            Token::Number(n) => {
+                self.next();
+                Ok(Expr::Number(n))
+            },
+            Token::Symbol("(") => {
+                self.next();
+                let value = self.expr()?;
+                self.expect(")")?;
+                Ok(value)
+            },
+            Token::Name(_) if matches!(self.peek_second(), Token::Symbol("(")) => {
+                let line = self.line();
+                let name = self.name()?;
+                self.expect("(")?;
+                let mut arguments = Vec::new();
+                while !self.accept(")") {
+                    if !arguments.is_empty() {
+                        self.expect(",")?;
+                    }
+                    arguments.push(self.expr()?);
+                }
+                self.calls.push((name.clone(), arguments.len(), line));
+                Ok(Expr::Call(name, arguments))
+            },
+            Token::Name(_) => Ok(Expr::Var(self.variable()?)),
+            other => self.error(format!("expected a value, found {}", other)),
+        }
+    }
+}
+
+/// A place in the code whose address is filled in once it is known
+#[derive(PartialEq, Copy, Clone, Debug)]
+struct Label(usize);
+
+/// An instruction parameter. Offsets are from the relative base.
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Arg {
+    Immediate(Word),
+    Relative(Word),
+    Label(Label),
+}
+
+#[derive(Default)]
+struct Assembler {
+    words: Vec<Word>,
+    labels: Vec<Option<usize>>,
+    /// Words to be replaced by the address of a label
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    fn place(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.words.len());
+    }
+
+    fn emit(&mut self, op: OpCode, args: &[Arg]) {
+        let mut instruction = op as Word;
+        let mut place_value = 100;
+        for arg in args {
+            instruction += place_value * match arg {
+                Arg::Relative(_) => 2,
+                _ => 1,
+            };
+            place_value *= 10;
+        }
+        self.words.push(instruction);
+
+        for arg in args {
+            match arg {
+                Arg::Immediate(value) | Arg::Relative(value) => self.words.push(*value),
+                Arg::Label(label) => {
+                    self.fixups.push((self.words.len(), *label));
+                    self.words.push(0);
+                },
+            }
+        }
+    }
+
+    fn jump(&mut self, label: Label) {
+        self.emit(OpCode::JumpIfNotZero, &[Arg::Immediate(1), Arg::Label(label)]);
+    }
+
+    fn copy(&mut self, from: Arg, to: Word) {
+        if from != Arg::Relative(to) {
+            self.emit(OpCode::Add, &[from, Arg::Immediate(0), Arg::Relative(to)]);
+        }
+    }
+
+    fn finish(mut self) -> Tape {
+        for (address, label) in &self.fixups {
+            self.words[*address] = self.labels[label.0].expect("label never placed") as Word;
+        }
+        Tape { contents: self.words }
+    }
+}
+
+impl Program {
+    pub fn compile(&self) -> Tape {
+        let mut assembler = Assembler::default();
+        let functions: HashMap<&str, Label> = self.functions.iter().map(|f| (f.name.as_str(), assembler.label())).collect();
+        let (stack, halt) = (assembler.label(), assembler.label());
+
+        // Call main with a frame at the start of the stack, returning to
+        // a halt
+        assembler.emit(OpCode::AdjustRelativeBase, &[Arg::Label(stack)]);
+        assembler.emit(OpCode::Add, &[Arg::Label(halt), Arg::Immediate(0), Arg::Relative(0)]);
+        assembler.jump(functions["main"]);
+        assembler.place(halt);
+        assembler.emit(OpCode::Halt, &[]);
+
+        for function in &self.functions {
+            assembler.place(functions[function.name.as_str()]);
+            let mut generator = Generator::new(&mut assembler, &functions, function);
+            generator.block(&function.body);
+            generator.ret(&Expr::Number(0));
+        }
+
+        assembler.place(stack);
+        assembler.finish()
+    }
+}
+
+/// Generates the code for one function
+struct Generator<'a> {
+    assembler: &'a mut Assembler,
+    functions: &'a HashMap<&'a str, Label>,
+    /// The frame offset of each variable visible in each enclosing block
+    scopes: Vec<HashMap<String, Word>>,
+    next_variable: Word,
+    /// The first offset free for working out expressions
+    temporaries: Word,
+}
+
+fn count_lets(statements: &[Statement]) -> Word {
+    statements.iter().map(|s| match s {
+        Statement::Let(..) => 1,
+        Statement::If(_, then, otherwise) => count_lets(then) + count_lets(otherwise),
+        Statement::While(_, body) => count_lets(body),
+        _ => 0,
+    }).sum()
+}
+
+impl<'a> Generator<'a> {
+    fn new(assembler: &'a mut Assembler, functions: &'a HashMap<&'a str, Label>, function: &Function) -> Generator<'a> {
+        let params = function.params.iter().enumerate().map(|(i, p)| (p.clone(), i as Word + 1)).collect();
+        let next_variable = function.params.len() as Word + 1;
+        Generator {
+            assembler,
+            functions,
+            scopes: vec![params],
+            next_variable,
+            temporaries: next_variable + count_lets(&function.body),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Word {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).cloned().expect("variables are checked by the parser")
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashMap::new());
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let next = self.temporaries;
+        match statement {
+            Statement::Let(name, value) => {
+                let offset = self.next_variable;
+                self.next_variable += 1;
+                self.expr(value, offset, next);
+                self.scopes.last_mut().unwrap().insert(name.clone(), offset);
+            },
+            Statement::Assign(name, value) => {
+                let offset = self.variable(name);
+                self.expr(value, offset, next);
+            },
+            Statement::If(condition, then, otherwise) => {
+                let (else_label, end) = (self.assembler.label(), self.assembler.label());
+                let condition = self.operand(condition, next);
+                self.assembler.emit(OpCode::JumpIfZero, &[condition, Arg::Label(else_label)]);
+                self.block(then);
+                if !otherwise.is_empty() {
+                    self.assembler.jump(end);
+                }
+                self.assembler.place(else_label);
+                self.block(otherwise);
+                self.assembler.place(end);
+            },
+            Statement::While(condition, body) => {
+                let (top, end) = (self.assembler.label(), self.assembler.label());
+                self.assembler.place(top);
+                let condition = self.operand(condition, next);
+                self.assembler.emit(OpCode::JumpIfZero, &[condition, Arg::Label(end)]);
+                self.block(body);
+                self.assembler.jump(top);
+                self.assembler.place(end);
+            },
+            Statement::Return(value) => self.ret(value.as_ref().unwrap_or(&Expr::Number(0))),
+            Statement::Expr(Expr::Call(name, arguments)) if name == "output" => {
+                let value = self.operand(&arguments[0], next);
+                self.assembler.emit(OpCode::ProduceOutput, &[value]);
+            },
+            Statement::Expr(value) => self.expr(value, next, next + 1),
+        }
+    }
+
+    /// Leaves the return value in the first slot after the return address
+    /// and jumps back to the caller
+    fn ret(&mut self, value: &Expr) {
+        self.expr(value, 1, self.temporaries);
+        self.assembler.emit(OpCode::JumpIfZero, &[Arg::Immediate(0), Arg::Relative(0)]);
+    }
+
+    /// Gives a parameter for an expression, working it out into `scratch`
+    /// unless it is a number or a variable. Offsets from `scratch` up are
+    /// free to use.
+    fn operand(&mut self, value: &Expr, scratch: Word) -> Arg {
+        match value {
+            Expr::Number(n) => Arg::Immediate(*n),
+            Expr::Var(name) => Arg::Relative(self.variable(name)),
+            _ => {
+                self.expr(value, scratch, scratch + 1);
+                Arg::Relative(scratch)
+            },
+        }
+    }
+
+    /// Works out an expression into the frame offset `target`, using
+    /// offsets from `next` up for anything in between. The target is only
+    /// written by the last instruction, so it can be read by the
+    /// expression.
+    fn expr(&mut self, value: &Expr, target: Word, next: Word) {
+        match value {
+            Expr::Number(_) | Expr::Var(_) => {
+                let value = self.operand(value, next);
+                self.assembler.copy(value, target);
+            },
+            Expr::Unary(op, operand) => {
+                let operand = self.operand(operand, next);
+                match op {
+                    UnaryOp::Neg => self.assembler.emit(OpCode::Mul, &[operand, Arg::Immediate(-1), Arg::Relative(target)]),
+                    UnaryOp::Not => self.assembler.emit(OpCode::Equal, &[operand, Arg::Immediate(0), Arg::Relative(target)]),
+                }
+            },
+            Expr::Binary(op @ BinaryOp::And, left, right) | Expr::Binary(op @ BinaryOp::Or, left, right) => {
+                // Work out whether each side is false, stopping early if
+                // that decides the answer
+                let end = self.assembler.label();
+                let left = self.operand(left, next);
+                self.assembler.emit(OpCode::Equal, &[left, Arg::Immediate(0), Arg::Relative(next)]);
+                let stop = if *op == BinaryOp::And { OpCode::JumpIfNotZero } else { OpCode::JumpIfZero };
+                self.assembler.emit(stop, &[Arg::Relative(next), Arg::Label(end)]);
+                let right = self.operand(right, next);
+                self.assembler.emit(OpCode::Equal, &[right, Arg::Immediate(0), Arg::Relative(next)]);
+                self.assembler.place(end);
+                self.assembler.emit(OpCode::Equal, &[Arg::Relative(next), Arg::Immediate(0), Arg::Relative(target)]);
+            },
+            Expr::Binary(op, left, right) => {
+                let left = self.operand(left, next);
+                let right = self.operand(right, next + 1);
+                let to = Arg::Relative(target);
+                let scratch = Arg::Relative(next + 1);
+                match op {
+                    BinaryOp::Add => self.assembler.emit(OpCode::Add, &[left, right, to]),
+                    BinaryOp::Sub => match right {
+                        Arg::Immediate(n) => self.assembler.emit(OpCode::Add, &[left, Arg::Immediate(-n), to]),
+                        _ => {
+                            self.assembler.emit(OpCode::Mul, &[right, Arg::Immediate(-1), scratch]);
+                            self.assembler.emit(OpCode::Add, &[left, scratch, to]);
+                        },
+                    },
+                    BinaryOp::Mul => self.assembler.emit(OpCode::Mul, &[left, right, to]),
+                    BinaryOp::Less => self.assembler.emit(OpCode::LessThan, &[left, right, to]),
+                    BinaryOp::Greater => self.assembler.emit(OpCode::LessThan, &[right, left, to]),
+                    BinaryOp::Equal => self.assembler.emit(OpCode::Equal, &[left, right, to]),
+                    BinaryOp::LessEqual | BinaryOp::GreaterEqual | BinaryOp::NotEqual => {
+                        // The opposite comparison, then not
+                        match op {
+                            BinaryOp::LessEqual => self.assembler.emit(OpCode::LessThan, &[right, left, scratch]),
+                            BinaryOp::GreaterEqual => self.assembler.emit(OpCode::LessThan, &[left, right, scratch]),
+                            _ => self.assembler.emit(OpCode::Equal, &[left, right, scratch]),
+                        }
+                        self.assembler.emit(OpCode::Equal, &[scratch, Arg::Immediate(0), to]);
+                    },
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            },
+            Expr::Call(name, arguments) if name == "input" && arguments.is_empty() => {
+                self.assembler.emit(OpCode::ConsumeInput, &[Arg::Relative(target)]);
+            },
+            Expr::Call(name, arguments) if name == "output" => {
+                let value = self.operand(&arguments[0], next);
+                self.assembler.emit(OpCode::ProduceOutput, &[value]);
+                self.assembler.copy(value, target);
+            },
+            Expr::Call(name, arguments) => {
+                // The callee's frame starts at `next`
+                for (i, argument) in arguments.iter().enumerate() {
+                    let offset = next + 1 + i as Word;
+                    self.expr(argument, offset, offset + 1);
+                }
+                let back = self.assembler.label();
+                self.assembler.emit(OpCode::Add, &[Arg::Label(back), Arg::Immediate(0), Arg::Relative(next)]);
+                self.assembler.emit(OpCode::AdjustRelativeBase, &[Arg::Immediate(next)]);
+                self.assembler.jump(self.functions[name.as_str()]);
+                self.assembler.place(back);
+                self.assembler.emit(OpCode::AdjustRelativeBase, &[Arg::Immediate(-next)]);
+                self.assembler.copy(Arg::Relative(next + 1), target);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::{Computer, CPUState};
+
+    fn run(source: &str, input: &[Word]) -> Vec<Word> {
+        let tape = compile(source).unwrap_or_else(|e| panic!("{}", e));
+        let mut computer = Computer::new_with_tape(&tape);
+        for value in input {
+            computer.io.add_input(*value);
+        }
+        computer.run();
+        assert_eq!(CPUState::Halted, computer.cpu_state());
+        computer.io.output
+    }
+
+    fn error(source: &str) -> String {
+        compile(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(vec![7, 9, -3, 14, 10, -2], run("
+            fn main() {
+                output(1 + 2 * 3);
+                output((1 + 2) * 3);
+                let a = input();
+                let b = input();
+                output(a - b);
+                output(2 * (a + b) - -2 - 2);
+                output(a * b);
+                output(-a);
+            }", &[2, 5]));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let source = "
+            fn main() {
+                let a = input();
+                let b = input();
+                output(a < b); output(a <= b); output(a > b); output(a >= b);
+                output(a == b); output(a != b); output(!a); output(a && b); output(a || b);
+            }";
+        assert_eq!(vec![1, 1, 0, 0, 0, 1, 0, 1, 1], run(source, &[1, 2]));
+        assert_eq!(vec![0, 1, 0, 1, 1, 0, 0, 1, 1], run(source, &[3, 3]));
+        assert_eq!(vec![0, 0, 1, 1, 0, 1, 1, 0, 1], run(source, &[0, -1]));
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // Each line outputs whatever shout does, then the result
+        assert_eq!(vec![0, 2, 1, 1, 0, 1], run("
+            fn shout(x) { output(x); return x; }
+            fn main() {
+                output(0 && shout(1));
+                output(1 && shout(2));
+                output(1 || shout(3));
+                output(0 || shout(0) || 1);
+            }", &[]));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            fn main() {
+                let n = input();
+                if n < 0 {
+                    output(-1);
+                } else if n == 0 {
+                    output(0);
+                } else {
+                    let total = 0;
+                    while n > 0 {
+                        total = total + n;
+                        n = n - 1;
+                    }
+                    output(total);
+                }
+            }";
+        assert_eq!(vec![-1], run(source, &[-5]));
+        assert_eq!(vec![0], run(source, &[0]));
+        assert_eq!(vec![55], run(source, &[10]));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(vec![55, 6765, 12], run("
+            fn fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+            fn add3(a, b, c) { return a + b + c; }
+            fn main() {
+                output(fib(10));
+                output(fib(20));
+                output(add3(fib(3), add3(1, 2, 3), 4));
+                nothing();
+            }
+            fn nothing() { return; }
+            ", &[]));
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(vec![2, 1, 3], run("
+            fn main() {
+                let x = 1;
+                if 1 {
+                    let x = x + 1;
+                    output(x);
+                }
+                output(x);
+                let x = 3;
+                output(x);
+            }", &[]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!("line 1: there's no main function", error("fn other() {}"));
+        assert_eq!("line 1: main can't take parameters", error("fn main(a) {}"));
+        assert_eq!("line 3: y isn't a variable", error("fn main() {\n let x = 1;\n x = y;\n}"));
+        assert_eq!("line 2: x isn't a variable", error("fn main() {\n if 1 { let x = 1; } output(x);\n}"));
+        assert_eq!("line 1: there's no function called f", error("fn main() { f(); }"));
+        assert_eq!("line 2: f takes 2 arguments, not 1", error("fn main() {\n output(f(1));\n}\nfn f(a, b) {}"));
+        assert_eq!("line 1: output is already defined", error("fn output(x) {} fn main() {}"));
+        assert_eq!("line 1: expected ;, found }", error("fn main() { output(1) }"));
+        assert_eq!("line 2: expected a value, found the end of the program", error("fn main() {\n output("));
+        assert_eq!("line 1: expected a value, found the end of the program", error("fn main() {"));
+        assert_eq!("line 1: unexpected %", error("fn main() { output(1 % 2); }"));
+    }
+}
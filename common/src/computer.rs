@@ -1,5 +1,16 @@
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::path::Path;
 use std::str::FromStr;
 
+use crate::binary;
+use crate::coverage::Coverage;
+use crate::instruction_set::{Instruction, InstructionSet, Role};
+use crate::protection::{Handler, MemoryProtection, Protection, Violation};
+use crate::recording::{IOEvent, Recording};
+use crate::self_modification::{Detector, Modification};
+use crate::tape_file::{TapeError, TapeFile};
+
 pub type Word = i64;
 pub type Reference = i64;
 
@@ -53,7 +64,7 @@ impl Computer {
     /// Creates a new computer with a halting program
     pub fn new() -> Computer {
         Computer {
-            memory: Memory { ram: vec![OpCode::Halt as Word], debug: false },
+            memory: Memory { ram: vec![OpCode::Halt as Word], debug: false, coverage: None, self_modification: None, protection: None, fault: None },
             cpu: CPU::new(),
             io: IOStream::new(),
             debug: false,
@@ -65,7 +76,7 @@ impl Computer {
     pub fn new_with_tape(tape: &Tape) -> Computer {
         let mut c = Computer::new();
         c.load_tape(tape);
-        return c;
+        c
     }
 
     /// Resets the computer and initialises the memory from the
@@ -79,6 +90,10 @@ impl Computer {
 
     fn load_tape(&mut self, tape: &Tape) {
         self.memory.ram = tape.contents.clone();
+        self.memory.fault = None;
+        if let Some(detector) = self.memory.self_modification.as_mut() {
+            detector.reset();
+        }
     }
 
     /// Runs the code in memory until it halts, returning the
@@ -88,10 +103,110 @@ impl Computer {
         self.memory.read_direct(0)
     }
 
+    /// Executes a single instruction, returning the state of the CPU
+    /// afterwards
+    pub fn step(&mut self) -> CPUState {
+        self.cpu.step(&mut self.memory, &mut self.io)
+    }
+
+    /// Replaces the instructions the computer understands, for example
+    /// with `InstructionSet::day_2()` to check a tape only uses the
+    /// original instructions
+    pub fn set_instruction_set(&mut self, instruction_set: InstructionSet) {
+        self.cpu.instruction_set = instruction_set;
+    }
+
+    /// Starts tracking which locations are executed, read and written.
+    /// Any previously gathered coverage is discarded. Coverage survives
+    /// `reset_and_load_tape` so that several runs can be combined.
+    pub fn enable_coverage(&mut self) {
+        self.memory.coverage = Some(Coverage::new());
+    }
+
+    /// Starts watching for the program writing to its own instructions.
+    /// If `fault` is true the computer stops with a fault when it happens,
+    /// otherwise it is only logged.
+    pub fn enable_self_modification_detection(&mut self, fault: bool) {
+        self.memory.self_modification = Some(Detector::new(fault));
+    }
+
+    /// Starts recording every input consumed and output produced, see
+    /// `recording::replay`. Like coverage, the recording survives
+    /// `reset_and_load_tape`.
+    pub fn enable_recording(&mut self) {
+        self.io.recording = Some(Recording::new());
+    }
+
     pub fn cpu_state(&self) -> CPUState {
         self.cpu.state
     }
 
+    /// The number of instructions executed since the tape was loaded
+    pub fn steps(&self) -> u64 {
+        self.cpu.steps
+    }
+
+    /// The address of the next instruction to execute
+    pub fn instruction_pointer(&self) -> Reference {
+        self.cpu.instruction_pointer
+    }
+
+    pub fn relative_base(&self) -> Reference {
+        self.cpu.relative_base
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            instruction_pointer: self.cpu.instruction_pointer,
+            instruction_address: self.cpu.instruction_address,
+            relative_base: self.cpu.relative_base,
+            steps: self.cpu.steps,
+            state: self.cpu.state,
+        }
+    }
+
+    /// The instruction most recently decoded, which is the one waiting
+    /// to be retried when the CPU is awaiting input
+    pub fn last_instruction(&self) -> Option<DecodedInstruction> {
+        self.cpu.last_instruction
+    }
+
+    /// Decodes the instruction at the instruction pointer without
+    /// executing it or recording any memory accesses. Returns `None` if
+    /// it isn't a valid instruction.
+    pub fn peek_next_instruction(&self) -> Option<InstructionInfo> {
+        self.peek_instruction(self.cpu.instruction_pointer)
+    }
+
+    /// Decodes the instruction at any address, resolving its operands
+    /// with the current relative base
+    pub fn peek_instruction(&self, address: Reference) -> Option<InstructionInfo> {
+        if address < 0 {
+            return None;
+        }
+        let instruction = DecodedInstruction::from_word(self.memory.read_direct(address), &self.cpu.instruction_set)?;
+
+        let mut operands = Vec::new();
+        for (i, role) in instruction.instruction.parameters.iter().enumerate() {
+            let parameter = self.memory.read_direct(address + 1 + i as Reference);
+            let mode = instruction.mode(i);
+            let location = match mode {
+                ParameterMode::Position => Some(parameter),
+                ParameterMode::Relative => Some(parameter + self.cpu.relative_base),
+                ParameterMode::Immediate if *role == Role::Write => Some(address + 1 + i as Reference),
+                ParameterMode::Immediate => None,
+            };
+            let value = match location {
+                Some(location) if location < 0 => None,
+                Some(location) => Some(self.memory.read_direct(location)),
+                None => Some(parameter),
+            };
+            operands.push(Operand { role: *role, mode, parameter, location, value });
+        }
+
+        Some(InstructionInfo { address, instruction, operands })
+    }
+
     pub fn debug_all(&mut self) {
         self.debug = true;
         self.cpu.debug = true;
@@ -107,11 +222,19 @@ impl Computer {
     }
 }
 
+impl Default for Computer {
+    fn default() -> Self {
+        Computer::new()
+    }
+}
+
 pub struct IOStream {
     pub debug: bool,
 
     input: Vec<Word>,
     pub output: Vec<Word>,
+    /// Every input consumed and output produced, if enabled
+    pub recording: Option<Recording>,
 }
 
 impl IOStream {
@@ -120,6 +243,7 @@ impl IOStream {
             input: Vec::new(),
             output: Vec::new(),
             debug: false,
+            recording: None,
         }
     }
 
@@ -127,23 +251,40 @@ impl IOStream {
         self.input.insert(0,value);
     }
 
+    /// The input not yet consumed, in the order it will be
+    pub fn pending_input(&self) -> Vec<Word> {
+        self.input.iter().rev().cloned().collect()
+    }
+
     pub fn reset(&mut self) {
         self.input = Vec::new();
         self.output = Vec::new();
     }
 
-    fn consume(&mut self) -> Option<Word> {
+    /// The input `n` places from the front of the queue, without
+    /// consuming it
+    fn peek(&self, n: usize) -> Option<Word> {
+        self.input.len().checked_sub(n + 1).map(|i| self.input[i])
+    }
+
+    fn consume(&mut self, step: u64) -> Option<Word> {
         let n = self.input.pop();
         if self.debug {
             println!("IO: consume {:?}", n);
         }
-        return n;
+        if let (Some(value), Some(recording)) = (n, self.recording.as_mut()) {
+            recording.events.push(IOEvent::Input { step, value });
+        }
+        n
     }
 
-    fn produce(&mut self, value: Word) {
+    fn produce(&mut self, value: Word, step: u64) {
         if self.debug {
             println!("IO: produce {}", value);
         }
+        if let Some(recording) = self.recording.as_mut() {
+            recording.events.push(IOEvent::Output { step, value });
+        }
         self.output.push(value);
     }
 }
@@ -152,9 +293,38 @@ impl IOStream {
 pub struct Memory {
     ram: Vec<Word>,
     pub debug: bool,
+    /// Tracks how each location has been accessed, if enabled
+    pub coverage: Option<Coverage>,
+    /// Watches for self modifying code, if enabled
+    pub self_modification: Option<Detector>,
+    /// Protected regions, if any have been set up
+    pub protection: Option<MemoryProtection>,
+
+    fault: Option<Fault>,
 }
 
 impl Memory {
+    /// The number of locations currently allocated. Reads beyond this
+    /// return 0 and writes grow the memory.
+    pub fn len(&self) -> usize {
+        self.ram.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ram.is_empty()
+    }
+
+    /// Every currently allocated location
+    pub fn contents(&self) -> &[Word] {
+        &self.ram
+    }
+
+    /// Copies out a range of locations, with anything outside memory,
+    /// including negative addresses, read as 0
+    pub fn export(&self, range: Range<Reference>) -> Vec<Word> {
+        range.map(|l| self.ram.get(l as usize).filter(|_| l >= 0).cloned().unwrap_or(0)).collect()
+    }
+
     /// Reads the current value of the passed location from memory
     pub fn read_direct(&self, location: Reference) -> Word {
         if self.debug {
@@ -199,23 +369,37 @@ impl Memory {
         self.write_direct(self.ram[location as usize] as Reference, value);
     }
 
-    fn read(&self, location: Reference, mode: ParameterMode, cpu: &CPU) -> Word {
+    /// Applies a protection to a range of locations. Only accesses made
+    /// by the CPU are checked, so the `_direct` and `_indirect` methods
+    /// can still be used to patch protected memory.
+    pub fn protect(&mut self, range: Range<Reference>, protection: Protection) {
+        self.protection.get_or_insert_with(MemoryProtection::new).add(range, protection);
+    }
+
+    /// Sets a handler called for every protection violation, which
+    /// returns true if the computer should fault
+    pub fn on_violation(&mut self, handler: Handler) {
+        self.protection.get_or_insert_with(MemoryProtection::new).set_handler(handler);
+    }
+
+    /// Reads an instruction from the passed location, noting it as
+    /// executed if coverage is being tracked
+    fn fetch(&mut self, location: Reference) -> Word {
+        self.track(Access::Fetch, location, location);
+        self.check_executing(location);
+        self.read_direct(location)
+    }
+
+    fn read(&mut self, location: Reference, mode: ParameterMode, cpu: &CPU) -> Word {
         if self.debug {
             println!("MEMORY: reading from {} with mode {:?}", location, mode);
         }
 
-        match mode {
-            ParameterMode::Position => self.read_indirect(location),
-            ParameterMode::Immediate => self.read_direct(location),
-            ParameterMode::Relative => {
-                let offset = self.read_direct(location);
-                let final_location = offset + cpu.relative_base;
-                if self.debug {
-                    println!("MEMORY: reading relative. Base {}, offset {}, result {}", cpu.relative_base, offset, final_location);
-                }
-                self.read_direct(final_location)
-            },
+        let address = self.resolve(location, mode, cpu);
+        if mode != ParameterMode::Immediate {
+            self.track(Access::Read, address, cpu.instruction_address);
         }
+        self.read_direct(address)
     }
 
     fn write(&mut self, location: Reference, value: Word, mode: ParameterMode, cpu: &CPU) {
@@ -223,32 +407,132 @@ impl Memory {
             println!("MEMORY: writing {} to {} with mode {:?}", value, location, mode);
         }
 
+        let address = self.resolve(location, mode, cpu);
+        self.track(Access::Write, address, cpu.instruction_address);
+        self.check_writing(address, cpu);
+
+        if self.fault.is_some() {
+            if self.debug {
+                println!("MEMORY: faulted, not writing");
+            }
+            return;
+        }
+        self.write_direct(address, value);
+    }
+
+    /// Works out the address a parameter refers to. The parameter
+    /// itself is always part of the instruction being executed.
+    fn resolve(&mut self, location: Reference, mode: ParameterMode, cpu: &CPU) -> Reference {
+        self.track(Access::Operand, location, cpu.instruction_address);
+        self.check_executing(location);
+
         match mode {
-            ParameterMode::Position => self.write_indirect(location, value),
-            ParameterMode::Immediate => self.write_direct(location, value),
+            ParameterMode::Position => self.read_direct(location),
+            ParameterMode::Immediate => location,
             ParameterMode::Relative => {
                 let offset = self.read_direct(location);
                 let final_location = offset + cpu.relative_base;
                 if self.debug {
-                    println!("MEMORY: writing relative. Base {}, offset {}, result {}", cpu.relative_base, offset, final_location);
+                    println!("MEMORY: relative. Base {}, offset {}, result {}", cpu.relative_base, offset, final_location);
                 }
-                self.write_direct(final_location, value)
+                final_location
             },
+        }
+    }
+
+    fn track(&mut self, access: Access, location: Reference, instruction: Reference) {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(access, location);
+        }
+
+        let violation = match self.protection.as_mut() {
+            Some(protection) => protection.check(access, location, instruction),
+            None => None,
+        };
+        if let Some(violation) = violation {
+            if self.debug {
+                println!("MEMORY: protection violation: {:?}", violation);
+            }
+            if self.fault.is_none() {
+                self.fault = Some(Fault::Protection(violation));
+            }
+        }
+    }
+
+    fn check_executing(&mut self, location: Reference) {
+        let modification = match self.self_modification.as_mut() {
+            Some(detector) => detector.executing(location),
+            None => None,
         };
+        self.self_modified(modification);
+    }
+
+    fn check_writing(&mut self, location: Reference, cpu: &CPU) {
+        if self.self_modification.is_none() {
+            return;
+        }
+        let opcode = self.read_direct(cpu.instruction_address);
+        let modification = self.self_modification.as_mut().and_then(|detector| detector.writing(location, cpu.instruction_address, opcode));
+        self.self_modified(modification);
+    }
+
+    fn self_modified(&mut self, modification: Option<Modification>) {
+        let modification = match modification {
+            Some(modification) => modification,
+            None => return,
+        };
+
+        if self.debug {
+            println!("MEMORY: self modification: {}", modification);
+        }
+        if self.self_modification.as_ref().is_some_and(|d| d.fault) && self.fault.is_none() {
+            self.fault = Some(Fault::SelfModification { writer: modification.writer, location: modification.location });
+        }
+    }
+
+    /// Returns and clears any fault raised while accessing memory
+    fn take_fault(&mut self) -> Option<Fault> {
+        self.fault.take()
     }
 }
 
+/// The ways in which the CPU can touch a memory location
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Access {
+    /// Read as the opcode of an instruction
+    Fetch,
+    /// Read as a parameter of an instruction
+    Operand,
+    /// Read as data by an instruction
+    Read,
+    /// Written as data by an instruction
+    Write,
+}
+
 /// A tape representing the initial memory state of an Intcode computer
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Tape {
     pub contents: Vec<Word>
 }
 
+impl Tape {
+    /// Loads a tape file, applying any patches in it. Bare tapes like
+    /// the puzzle inputs are tape files too, see `TapeFile` for the rest
+    /// of the format. Binary tapes written by `binary::encode` are
+    /// recognised and loaded too.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Tape, TapeError> {
+        if binary::is_binary_file(&path)? {
+            return binary::load(path);
+        }
+        Ok(TapeFile::load(path)?.tape())
+    }
+}
+
 impl FromStr for Tape {
-    type Err = ();
+    type Err = ParseIntError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let data = s.trim().split(",").map(|i| i.parse::<Word>().unwrap() ).collect();
+        let data = s.trim().split(",").map(|i| i.parse::<Word>()).collect::<Result<_, _>>()?;
         Ok(Tape { contents: data } )
     }
 }
@@ -258,29 +542,68 @@ pub enum CPUState {
     AwaitingInstruction,
     AwaitingInput,
     Halted,
+    /// Stopped because the program did something it was not allowed to.
+    /// The instruction pointer is left on the offending instruction.
+    Faulted(Fault),
 }
 
-#[derive(Copy, Clone, Debug)]
-enum OpCode {
-    Add,
-    Mul,
+/// Reasons a computer can stop with `CPUState::Faulted`
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Fault {
+    /// The instruction at `writer` modified the instruction stream at
+    /// `location`, see `Computer::enable_self_modification_detection`
+    SelfModification { writer: Reference, location: Reference },
+    /// An access tripped a protected region, see `Memory::protect`
+    Protection(Violation),
+    /// The word at `address` isn't an instruction in the computer's
+    /// instruction set
+    InvalidInstruction { address: Reference, instruction: Word },
+}
+
+/// The opcodes of the standard Intcode instruction set
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum OpCode {
+    Add = 1,
+    Mul = 2,
 
-    ConsumeInput,
-    ProduceOutput,
+    ConsumeInput = 3,
+    ProduceOutput = 4,
 
-    JumpIfNotZero,
-    JumpIfZero,
+    JumpIfNotZero = 5,
+    JumpIfZero = 6,
 
-    LessThan,
-    Equal,
+    LessThan = 7,
+    Equal = 8,
 
-    AdjustRelativeBase,
+    AdjustRelativeBase = 9,
 
-    Halt,
+    Halt = 99,
 }
 
-#[derive(Copy, Clone, Debug)]
-enum ParameterMode {
+impl OpCode {
+    /// The opcode with the passed number, ignoring any modes
+    pub fn from_word(op: Word) -> Option<OpCode> {
+        match op {
+            1 => Some(OpCode::Add),
+            2 => Some(OpCode::Mul),
+            3 => Some(OpCode::ConsumeInput),
+            4 => Some(OpCode::ProduceOutput),
+            5 => Some(OpCode::JumpIfNotZero),
+            6 => Some(OpCode::JumpIfZero),
+            7 => Some(OpCode::LessThan),
+            8 => Some(OpCode::Equal),
+            9 => Some(OpCode::AdjustRelativeBase),
+
+            99 => Some(OpCode::Halt),
+
+            _ => None,
+        }
+    }
+}
+
+/// How a parameter is turned into a value or a location
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ParameterMode {
     Position,
     Immediate,
     Relative,
@@ -298,14 +621,17 @@ impl ParameterMode {
 
 pub struct CPU {
     instruction_pointer: Reference,
+    instruction_address: Reference,
     state: CPUState,
     relative_base: Reference,
+    steps: u64,
     last_instruction: Option<DecodedInstruction>,
+    instruction_set: InstructionSet,
     pub debug: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
-struct OpModes {
+pub(crate) struct OpModes {
     p0_mode: ParameterMode,
     p1_mode: ParameterMode,
     p2_mode: ParameterMode,
@@ -321,384 +647,315 @@ impl OpModes {
             p3_mode: ParameterMode::Position,
         }
     }
+
+    pub(crate) fn mode(&self, index: usize) -> ParameterMode {
+        match index {
+            0 => self.p0_mode,
+            1 => self.p1_mode,
+            2 => self.p2_mode,
+            3 => self.p3_mode,
+            _ => ParameterMode::Position,
+        }
+    }
 }
 
 impl FromStr for OpModes {
     type Err = ();
 
+    /// Reads the modes from the rightmost digit, so any digits beyond
+    /// the fourth parameter are ignored
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let result = match s.len() {
-            4 => OpModes {
-                p0_mode: ParameterMode::from_char(s.chars().nth(3).unwrap()),
-                p1_mode: ParameterMode::from_char(s.chars().nth(2).unwrap()),
-                p2_mode: ParameterMode::from_char(s.chars().nth(1).unwrap()),
-                p3_mode: ParameterMode::from_char(s.chars().nth(0).unwrap()),
-            },
-            3 => OpModes {
-                p0_mode: ParameterMode::from_char(s.chars().nth(2).unwrap()),
-                p1_mode: ParameterMode::from_char(s.chars().nth(1).unwrap()),
-                p2_mode: ParameterMode::from_char(s.chars().nth(0).unwrap()),
-                p3_mode: ParameterMode::Position,
-            },
-            2 => OpModes {
-                p0_mode: ParameterMode::from_char(s.chars().nth(1).unwrap()),
-                p1_mode: ParameterMode::from_char(s.chars().nth(0).unwrap()),
-                p2_mode: ParameterMode::Position,
-                p3_mode: ParameterMode::Position,
-            },
-            1 => OpModes {
-                p0_mode: ParameterMode::from_char(s.chars().nth(0).unwrap()),
-                p1_mode: ParameterMode::Position,
-                p2_mode: ParameterMode::Position,
-                p3_mode: ParameterMode::Position,
-            },
-            _ => OpModes {
-                p0_mode: ParameterMode::Position,
-                p1_mode: ParameterMode::Position,
-                p2_mode: ParameterMode::Position,
-                p3_mode: ParameterMode::Position,
-            },
+        let mut modes = s.chars().rev().map(ParameterMode::from_char);
+        let mut next = || modes.next().unwrap_or(ParameterMode::Position);
+
+        let result = OpModes {
+            p0_mode: next(),
+            p1_mode: next(),
+            p2_mode: next(),
+            p3_mode: next(),
         };
 
         Ok(result)
     }
 }
 
+/// An instruction word split into the instruction and its parameter modes
 #[derive(Debug, Copy, Clone)]
-struct DecodedInstruction {
-    operation: OpCode,
-    modes: OpModes,
+pub struct DecodedInstruction {
+    pub(crate) instruction: Instruction,
+    pub(crate) modes: OpModes,
 }
 
-impl CPU {
-    fn new() -> CPU {
-        CPU { instruction_pointer: 0, state: CPUState::AwaitingInstruction, relative_base: 0, last_instruction: None, debug: false }
-    }
-
-    fn reset(&mut self) {
-        self.instruction_pointer = 0;
-        self.state = CPUState::AwaitingInstruction;
-        self.relative_base = 0;
-        self.last_instruction = None;
-    }
-
-    fn execute_instruction(&mut self, memory: &mut Memory, io: &mut IOStream) -> CPUState {
-        let location = self.consume_ip();
-        let op = self.decode(memory.read_direct(location));
-        if self.debug {
-            println!("CPU: STEP DECODED: {:?}", op);
-        }
-
-        self.last_instruction = Some(op.clone());
-
-        match op.operation {
-            OpCode::Add => self.op_add(memory, &op.modes),
-            OpCode::Mul => self.op_mul(memory, &op.modes),
-            OpCode::ConsumeInput => self.op_consume_input(memory, io, &op.modes),
-            OpCode::ProduceOutput => self.op_produce_output(memory, io, &op.modes),
-            OpCode::JumpIfNotZero => self.op_jump_not_zero(memory, &op.modes),
-            OpCode::JumpIfZero => self.op_jump_zero(memory, &op.modes),
-            OpCode::LessThan => self.op_less_than(memory, &op.modes),
-            OpCode::Equal => self.op_equal(memory, &op.modes),
-            OpCode::AdjustRelativeBase => self.op_adjust_relative_base(memory, &op.modes),
-
-            OpCode::Halt => {
-                if self.debug {
-                    println!("CPU: halting");
-                }
-                CPUState::Halted
-            },
-        }
-    }
-
-    fn step(&mut self, memory: &mut Memory, io: &mut IOStream) -> CPUState {
-        if self.debug {
-            println!("CPU: STEP STATE: {:?}", self.state);
-        }
-
-        let old_state = self.state;
-        self.state = match self.state {
-            CPUState::Halted => CPUState::Halted,
-            CPUState::AwaitingInput => self.op_consume_input(memory, io, &self.last_instruction.unwrap().modes),
-            CPUState::AwaitingInstruction => self.execute_instruction(memory, io),
-        };
-
-        if self.debug && old_state != self.state {
-            println!("CPU: state changed from {:?} to {:?}", old_state, self.state);
-        }
-
-        self.state
-    }
-
-    fn decode_op(op: Word) -> OpCode {
-        match op {
-            1 => OpCode::Add,
-            2 => OpCode::Mul,
-            3 => OpCode::ConsumeInput,
-            4 => OpCode::ProduceOutput,
-            5 => OpCode::JumpIfNotZero,
-            6 => OpCode::JumpIfZero,
-            7 => OpCode::LessThan,
-            8 => OpCode::Equal,
-            9 => OpCode::AdjustRelativeBase,
-
-            99 => OpCode::Halt,
-
-            _ => panic!("Unrecognised instruction!"),
-        }
-    }
-
-    fn decode(&self, instruction: Word) -> DecodedInstruction {
-        if self.debug {
-            println!("CPU: DECODE: {}", instruction);
+impl DecodedInstruction {
+    /// Decodes an instruction word using the passed instruction set,
+    /// returning `None` if it isn't a recognised instruction
+    pub fn from_word(instruction: Word, instruction_set: &InstructionSet) -> Option<DecodedInstruction> {
+        if instruction < 0 {
+            return None;
         }
 
         let s = instruction.to_string();
 
         if s.len() <= 2 {
-            return DecodedInstruction {
-                operation: CPU::decode_op(instruction),
+            return Some(DecodedInstruction {
+                instruction: *instruction_set.get(instruction)?,
                 modes: OpModes::new(),
-            };
+            });
         }
 
-        let operation = CPU::decode_op(s[s.len() - 2 .. ].parse().unwrap());
+        let instruction = *instruction_set.get(s[s.len() - 2 .. ].parse().unwrap())?;
         let modes = s[ .. s.len() - 2].parse().unwrap();
 
-        DecodedInstruction { operation, modes }
+        Some(DecodedInstruction { instruction, modes })
     }
 
-    fn consume_ip(&mut self) -> Reference {
-        self.instruction_pointer += 1;
-        self.instruction_pointer - 1
+    pub fn instruction(&self) -> &Instruction {
+        &self.instruction
     }
 
-    fn op_add(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b= self.consume_ip();
-        let param_c = self.consume_ip();
-
-        if self.debug {
-            println!("CPU: a: {}, b: {}, c: {}", param_a, param_b, param_c);
-        }
-
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
-        let result = a + b;
-
-        if self.debug {
-            println!("CPU: read {}, read {}, writing {}", a, b, result);
-        }
+    /// The standard opcode, or `None` for instructions from a dialect
+    pub fn opcode(&self) -> Option<OpCode> {
+        OpCode::from_word(self.instruction.opcode)
+    }
 
-        memory.write(param_c, result,modes.p2_mode, &self);
+    pub fn mnemonic(&self) -> &'static str {
+        self.instruction.mnemonic
+    }
 
-        CPUState::AwaitingInstruction
+    /// The mode of a parameter, numbered from 0
+    pub fn mode(&self, parameter: usize) -> ParameterMode {
+        self.modes.mode(parameter)
     }
 
-    fn op_mul(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b= self.consume_ip();
-        let param_c = self.consume_ip();
+    /// The number of memory locations taken up by the instruction
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        1 + self.instruction.parameters.len()
+    }
+}
 
-        if self.debug {
-            println!("CPU: a: {}, b: {}, c: {}", param_a, param_b, param_c);
-        }
+/// A snapshot of the CPU's registers
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Registers {
+    /// The address of the next instruction to execute
+    pub instruction_pointer: Reference,
+    /// The address of the instruction most recently executed
+    pub instruction_address: Reference,
+    pub relative_base: Reference,
+    /// The number of instructions executed since the tape was loaded
+    pub steps: u64,
+    pub state: CPUState,
+}
 
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
-        let result = a * b;
+/// One parameter of an instruction in memory
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Operand {
+    pub role: Role,
+    pub mode: ParameterMode,
+    /// The parameter as it appears in memory
+    pub parameter: Word,
+    /// The location the parameter refers to, or `None` in immediate mode
+    /// for parameters which are read
+    pub location: Option<Reference>,
+    /// The value the instruction would read, or the value currently at
+    /// the location it would write to. `None` for negative locations.
+    pub value: Option<Word>,
+}
 
-        if self.debug {
-            println!("CPU: read {}, read {}, writing {}", a, b, result);
-        }
+/// The instruction at an address, decoded along with its operands as
+/// they would be if it was executed now
+#[derive(Debug, Clone)]
+pub struct InstructionInfo {
+    pub address: Reference,
+    pub instruction: DecodedInstruction,
+    pub operands: Vec<Operand>,
+}
 
-        memory.write(param_c, result, modes.p2_mode, &self);
+/// What an instruction handler can see and do while it executes. The
+/// parameters are numbered from 0 in the order they follow the opcode.
+///
+/// Input and output only reach the IO stream once the instruction has
+/// finished, so an instruction which faults part way through takes no
+/// input and produces no output, and leaves the relative base alone.
+pub struct Execution<'a> {
+    cpu: &'a mut CPU,
+    memory: &'a mut Memory,
+    io: &'a mut IOStream,
+    modes: OpModes,
+    /// The number of inputs taken so far
+    inputs: usize,
+    outputs: Vec<Word>,
+}
 
-        CPUState::AwaitingInstruction
+impl<'a> Execution<'a> {
+    /// Reads the value of a parameter, following its mode
+    pub fn read(&mut self, parameter: usize) -> Word {
+        let location = self.parameter_location(parameter);
+        self.memory.read(location, self.modes.mode(parameter), self.cpu)
     }
 
-    fn op_consume_input(&mut self, memory: &mut Memory, io: &mut IOStream, modes: &OpModes) -> CPUState {
-        let value = io.consume();
+    /// Writes to the location a parameter refers to, following its mode
+    pub fn write(&mut self, parameter: usize, value: Word) {
+        let location = self.parameter_location(parameter);
+        self.memory.write(location, value, self.modes.mode(parameter), self.cpu);
+    }
 
-        if value.is_none() {
-            if self.debug {
-                println!("CPU: no value to consume, waiting");
-            }
-            return CPUState::AwaitingInput;
+    /// Takes the next input, if there is one. Handlers which get `None`
+    /// should return `CPUState::AwaitingInput` without doing anything
+    /// else, and will be called again once input is available.
+    pub fn input(&mut self) -> Option<Word> {
+        if self.memory.fault.is_some() {
+            return None;
         }
+        let value = self.io.peek(self.inputs)?;
+        self.inputs += 1;
+        Some(value)
+    }
 
-        let dest = self.consume_ip();
-
-        if self.debug {
-            println!("CPU: consumed {}, writing to {}", value.unwrap(), dest);
+    pub fn output(&mut self, value: Word) {
+        if self.memory.fault.is_none() {
+            self.outputs.push(value);
         }
-
-        memory.write(dest, value.unwrap(), modes.p0_mode, &self);
-
-        CPUState::AwaitingInstruction
     }
 
-    fn op_produce_output(&mut self, memory: &mut Memory, io: &mut IOStream, modes: &OpModes) -> CPUState {
-        let source = self.consume_ip();
-        let value = memory.read(source, modes.p0_mode, &self);
-
-        if self.debug {
-            println!("CPU: read from {}, producing {}", source, value);
+    /// Continues execution from the passed address rather than the
+    /// following instruction
+    pub fn jump(&mut self, target: Reference) {
+        if self.memory.fault.is_none() {
+            self.cpu.instruction_pointer = target;
         }
-
-        io.produce(value);
-
-        CPUState::AwaitingInstruction
     }
 
-    fn op_jump_not_zero(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b = self.consume_ip();
+    pub fn relative_base(&self) -> Reference {
+        self.cpu.relative_base
+    }
 
-        if self.debug {
-            println!("CPU: a: {}, b: {}", param_a, param_b);
+    pub fn adjust_relative_base(&mut self, offset: Word) {
+        if self.memory.fault.is_none() {
+            self.cpu.relative_base += offset;
         }
+    }
 
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
-
-        if self.debug {
-            println!("CPU: read {}, read {}", a, b)
-        }
+    pub fn debug(&self) -> bool {
+        self.cpu.debug
+    }
 
-        if a != 0 {
-            if self.debug {
-                println!("CPU: adjusting IP to {} as {} != 0", b, a);
-            }
+    fn parameter_location(&self, parameter: usize) -> Reference {
+        self.cpu.instruction_address + 1 + parameter as Reference
+    }
+}
 
-            self.instruction_pointer = b as Reference;
-        } else if self.debug {
-            println!("CPU: not adjusting IP as {} == 0", a);
+impl CPU {
+    fn new() -> CPU {
+        CPU {
+            instruction_pointer: 0,
+            instruction_address: 0,
+            state: CPUState::AwaitingInstruction,
+            relative_base: 0,
+            steps: 0,
+            last_instruction: None,
+            instruction_set: InstructionSet::intcode(),
+            debug: false,
         }
+    }
 
-        CPUState::AwaitingInstruction
+    fn reset(&mut self) {
+        self.instruction_pointer = 0;
+        self.instruction_address = 0;
+        self.state = CPUState::AwaitingInstruction;
+        self.relative_base = 0;
+        self.steps = 0;
+        self.last_instruction = None;
     }
 
-    fn op_jump_zero(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b = self.consume_ip();
+    fn execute_instruction(&mut self, memory: &mut Memory, io: &mut IOStream) -> CPUState {
+        let location = self.instruction_pointer;
+        self.instruction_address = location;
 
-        if self.debug {
-            println!("CPU: a: {}, b: {}", param_a, param_b);
+        let instruction = memory.fetch(location);
+        if memory.fault.is_some() {
+            // Don't try to decode it, step will report the fault
+            return CPUState::AwaitingInstruction;
         }
 
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
-
+        let op = match self.decode(instruction) {
+            Some(op) => op,
+            None => return CPUState::Faulted(Fault::InvalidInstruction { address: location, instruction }),
+        };
         if self.debug {
-            println!("CPU: read {}, read {}", a, b)
-        }
-
-        if a == 0 {
-            if self.debug {
-                println!("CPU: adjusting IP to {} as {} == 0", b, a);
-            }
-
-            self.instruction_pointer = b as Reference;
-        } else if self.debug {
-            println!("CPU: not adjusting IP as {} != 0", a);
+            println!("CPU: STEP DECODED: {:?}", op);
         }
 
-        CPUState::AwaitingInstruction
+        self.last_instruction = Some(op);
+        self.execute(op, memory, io)
     }
 
-    fn op_less_than(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b= self.consume_ip();
-        let param_c = self.consume_ip();
+    /// Runs the handler for a decoded instruction. If it is waiting for
+    /// input the instruction pointer is left on it, ready to try again.
+    /// If it faults nothing it did is kept, and `step` reports the fault.
+    fn execute(&mut self, op: DecodedInstruction, memory: &mut Memory, io: &mut IOStream) -> CPUState {
+        self.instruction_pointer = self.instruction_address + op.len() as Reference;
+        let relative_base = self.relative_base;
 
-        if self.debug {
-            println!("CPU: a: {}, b: {}, c: {}", param_a, param_b, param_c);
-        }
-
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
+        let mut execution = Execution { cpu: self, memory, io, modes: op.modes, inputs: 0, outputs: Vec::new() };
+        let state = (op.instruction.handler)(&mut execution);
+        let (inputs, outputs) = (execution.inputs, execution.outputs);
 
-        if self.debug {
-            println!("CPU: read {}, read {}",a, b);
+        if memory.fault.is_some() {
+            self.relative_base = relative_base;
+            return state;
+        }
+        for _ in 0 .. inputs {
+            io.consume(self.steps);
+        }
+        for value in outputs {
+            io.produce(value, self.steps);
         }
 
-        let output =  if a < b {
-            if self.debug {
-                println!("CPU: {} < {}", a, b)
-            }
-
-            1
+        if state == CPUState::AwaitingInput {
+            self.instruction_pointer = self.instruction_address;
         } else {
-            if self.debug {
-                println!("CPU: {} >= {}", a, b)
-            }
-
-            0
-        };
-
-        if self.debug {
-            println!("CPU: writing {} to {}", output, param_c);
+            self.steps += 1;
         }
-
-        memory.write(param_c, output, modes.p2_mode, &self);
-
-        CPUState::AwaitingInstruction
+        state
     }
 
-    fn op_equal(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let param_a = self.consume_ip();
-        let param_b= self.consume_ip();
-        let param_c = self.consume_ip();
-
+    fn step(&mut self, memory: &mut Memory, io: &mut IOStream) -> CPUState {
         if self.debug {
-            println!("CPU: a: {}, b: {}, c: {}", param_a, param_b, param_c);
+            println!("CPU: STEP STATE: {:?}", self.state);
         }
 
-        let a = memory.read(param_a, modes.p0_mode, &self);
-        let b = memory.read(param_b, modes.p1_mode, &self);
+        let old_state = self.state;
+        self.state = match self.state {
+            CPUState::Halted => CPUState::Halted,
+            CPUState::Faulted(fault) => CPUState::Faulted(fault),
+            CPUState::AwaitingInput => self.execute(self.last_instruction.unwrap(), memory, io),
+            CPUState::AwaitingInstruction => self.execute_instruction(memory, io),
+        };
 
-        if self.debug {
-            println!("CPU: read {}, read {}",a, b);
+        if let Some(fault) = memory.take_fault() {
+            self.state = CPUState::Faulted(fault);
         }
-
-        let output =  if a == b {
-            if self.debug {
-                println!("CPU: {} == {}", a, b)
+        if let CPUState::Faulted(fault) = self.state {
+            if self.debug && old_state != self.state {
+                println!("CPU: fault {:?} at {}", fault, self.instruction_address);
             }
-
-            1
-        } else {
-            if self.debug {
-                println!("CPU: {} != {}", a, b)
-            }
-
-            0
-        };
-
-        if self.debug {
-            println!("CPU: writing {} to {}", output, param_c);
+            self.instruction_pointer = self.instruction_address;
         }
 
-        memory.write(param_c, output, modes.p2_mode, &self);
-
-        CPUState::AwaitingInstruction
-    }
-
-    fn op_adjust_relative_base(&mut self, memory: &mut Memory, modes: &OpModes) -> CPUState {
-        let a = memory.read(self.consume_ip(), modes.p0_mode, &self);
-
-        if self.debug {
-            print!("CPU: adjusting relative base {} by {} to ", self.relative_base, a);
+        if self.debug && old_state != self.state {
+            println!("CPU: state changed from {:?} to {:?}", old_state, self.state);
         }
 
-        self.relative_base = self.relative_base + a;
+        self.state
+    }
 
+    fn decode(&self, instruction: Word) -> Option<DecodedInstruction> {
         if self.debug {
-            println!("{}", self.relative_base);
+            println!("CPU: DECODE: {}", instruction);
         }
 
-        CPUState::AwaitingInstruction
+        DecodedInstruction::from_word(instruction, &self.instruction_set)
     }
 }
 
@@ -722,6 +979,18 @@ mod tests {
         assert_eq!(3, tape.contents[2]);
     }
 
+    #[test]
+    fn test_tape_with_bad_word() {
+        assert!("1,2,x".parse::<Tape>().is_err());
+        assert!("".parse::<Tape>().is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let computer = Computer::new_with_tape(&"1,2,3".parse().unwrap());
+        assert_eq!(vec![0, 0, 1, 2, 3, 0], computer.memory.export(-2 .. 4));
+    }
+
     #[test]
     fn test_day_02_a_works() {
         let input = "1,0,0,3,1,1,2,3,1,3,4,3,1,5,0,3,2,1,6,19,1,9,19,23,2,23,10,27,1,27,5,31,1,31,6,35,1,6,35,39,2,39,13,43,1,9,43,47,2,9,47,51,1,51,6,55,2,55,10,59,1,59,5,63,2,10,63,67,2,9,67,71,1,71,5,75,2,10,75,79,1,79,6,83,2,10,83,87,1,5,87,91,2,9,91,95,1,95,5,99,1,99,2,103,1,103,13,0,99,2,14,0,0";
@@ -760,123 +1029,6 @@ mod tests {
         assert_eq!(1002, computer.run());
     }
 
-    #[test]
-    fn test_example_day5_b_1() {
-        let mut computer = Computer::new();
-        let tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(1);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(8);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_2() {
-        let mut computer = Computer::new();
-        let tape = "3,9,7,9,10,9,4,9,99,-1,8".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(1);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(100);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_4() {
-        let mut computer = Computer::new();
-        let tape = "3,3,1108,-1,8,3,4,3,99".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(1);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(8);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_5() {
-        let mut computer = Computer::new();
-        let tape = "3,3,1107,-1,8,3,4,3,99".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(1);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(800);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_6() {
-        let mut computer = Computer::new();
-        let tape = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(0);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(800);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_7() {
-        let mut computer = Computer::new();
-        let tape = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(0);
-        computer.run();
-        assert_eq!(0, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(800);
-        computer.run();
-        assert_eq!(1, computer.io.output[0]);
-    }
-
-    #[test]
-    fn test_example_day5_b_8() {
-        let mut computer = Computer::new();
-        let tape = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99".parse().unwrap();
-
-        computer.load_tape(&tape);
-        computer.io.add_input(0);
-        computer.run();
-        assert_eq!(999, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(8);
-        computer.run();
-        assert_eq!(1000, computer.io.output[0]);
-
-        computer.reset_and_load_tape(&tape);
-        computer.io.add_input(800);
-        computer.run();
-        assert_eq!(1001, computer.io.output[0]);
-    }
-
     #[test]
     fn test_example_day9_1() {
         let mut computer = Computer::new();
@@ -906,4 +1058,43 @@ mod tests {
         computer.run();
         assert_eq!(1125899906842624, computer.io.output[0]);
     }
+
+    #[test]
+    fn test_registers_and_peek() {
+        let mut computer = Computer::new_with_tape(&"109,10,21101,2,3,-5,3,11,99".parse().unwrap());
+        computer.step();
+
+        let registers = computer.registers();
+        assert_eq!(Registers { instruction_pointer: 2, instruction_address: 0, relative_base: 10, steps: 1, state: CPUState::AwaitingInstruction }, registers);
+        assert_eq!(Some(OpCode::AdjustRelativeBase), computer.last_instruction().unwrap().opcode());
+
+        let next = computer.peek_next_instruction().unwrap();
+        assert_eq!(2, next.address);
+        assert_eq!("ADD", next.instruction.mnemonic());
+        assert_eq!(4, next.instruction.len());
+        assert_eq!(vec![
+            Operand { role: Role::Read, mode: ParameterMode::Immediate, parameter: 2, location: None, value: Some(2) },
+            Operand { role: Role::Read, mode: ParameterMode::Immediate, parameter: 3, location: None, value: Some(3) },
+            Operand { role: Role::Write, mode: ParameterMode::Relative, parameter: -5, location: Some(5), value: Some(-5) },
+        ], next.operands);
+
+        // Peeking doesn't change anything
+        assert_eq!(registers, computer.registers());
+        computer.step();
+        assert_eq!(5, computer.memory.read_direct(5));
+
+        computer.step();
+        assert_eq!(CPUState::AwaitingInput, computer.cpu_state());
+        assert_eq!(Some(OpCode::ConsumeInput), computer.last_instruction().unwrap().opcode());
+        assert_eq!(6, computer.registers().instruction_pointer);
+        assert!(computer.peek_instruction(8).unwrap().operands.is_empty());
+        assert!(computer.peek_instruction(1).is_none());
+    }
+
+    #[test]
+    fn test_modes_beyond_last_parameter_ignored() {
+        let mut computer = Computer::new_with_tape(&"1001101,3,4,5,99,0".parse().unwrap());
+        computer.run();
+        assert_eq!(7, computer.memory.read_direct(5));
+    }
 }
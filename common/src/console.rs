@@ -0,0 +1,220 @@
+use std::io::{self, BufRead, Write};
+
+use crate::computer::{Computer, CPUState, Word};
+
+/// Reads a line of input for a tape. A line of numbers separated by
+/// commas or spaces gives those numbers. Anything else is ASCII text, given
+/// one character at a time followed by a newline, and a leading `"` forces
+/// text so that `"12` gives `'1', '2', '\n'`.
+pub fn parse_input(line: &str) -> Result<Vec<Word>, String> {
+    if let Some(text) = line.strip_prefix('"') {
+        return ascii(text);
+    }
+    let numbers: Result<Vec<Word>, _> = line.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.parse())
+        .collect();
+    match numbers {
+        Ok(numbers) if !numbers.is_empty() => Ok(numbers),
+        _ => ascii(line),
+    }
+}
+
+fn ascii(text: &str) -> Result<Vec<Word>, String> {
+    if let Some(c) = text.chars().find(|c| !c.is_ascii()) {
+        return Err(format!("{} isn't ASCII", c));
+    }
+    Ok(text.bytes().map(Word::from).chain(Some(10)).collect())
+}
+
+/// Outputs as text if they're all printable ASCII, otherwise as numbers
+pub fn describe_output(output: &[Word]) -> String {
+    let printable = |w: &Word| *w == 10 || (32 .. 127).contains(w);
+    if !output.is_empty() && output.iter().all(printable) {
+        output.iter().map(|w| *w as u8 as char).collect()
+    } else {
+        let words: Vec<String> = output.iter().map(|w| w.to_string()).collect();
+        words.join(",")
+    }
+}
+
+/// Runs a computer, asking for input on a terminal whenever the tape
+/// waits for it. Each prompt shows the instruction pointer and anything
+/// output since the last one.
+///
+/// As well as input, these commands are understood:
+///
+/// - `!!` repeats the last input
+/// - `!n` repeats input `n` from the history
+/// - `:history` lists previous input
+/// - `:quit` stops without giving any more input
+pub struct Console<R, W> {
+    reader: R,
+    writer: W,
+    /// Every line given as input, oldest first
+    pub history: Vec<String>,
+    /// How much output has been shown
+    shown: usize,
+}
+
+impl Console<io::StdinLock<'static>, io::Stdout> {
+    pub fn stdio() -> Self {
+        Console::new(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Console { reader, writer, history: Vec::new(), shown: 0 }
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Runs the computer until it stops for any reason other than waiting
+    /// for input, or until the input runs out or the user quits, returning
+    /// the state it stopped in
+    pub fn run(&mut self, computer: &mut Computer) -> io::Result<CPUState> {
+        loop {
+            computer.run();
+            self.show_output(computer)?;
+            let state = computer.cpu_state();
+            if state != CPUState::AwaitingInput {
+                return Ok(state);
+            }
+
+            match self.read_input(computer)? {
+                Some(input) => input.into_iter().for_each(|value| computer.io.add_input(value)),
+                None => return Ok(state),
+            }
+        }
+    }
+
+    fn show_output(&mut self, computer: &Computer) -> io::Result<()> {
+        let output = &computer.io.output[self.shown.min(computer.io.output.len()) ..];
+        if !output.is_empty() {
+            let text = describe_output(output);
+            write!(self.writer, "{}", text)?;
+            if !text.ends_with('\n') {
+                writeln!(self.writer)?;
+            }
+        }
+        self.shown = computer.io.output.len();
+        Ok(())
+    }
+
+    /// Prompts until the user gives some input, returning `None` at the
+    /// end of the input or on `:quit`
+    fn read_input(&mut self, computer: &Computer) -> io::Result<Option<Vec<Word>>> {
+        loop {
+            write!(self.writer, "[{}] input> ", computer.instruction_pointer())?;
+            self.writer.flush()?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                writeln!(self.writer)?;
+                return Ok(None);
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            let line = match line.trim() {
+                ":quit" => return Ok(None),
+                ":history" => {
+                    for (i, entry) in self.history.iter().enumerate() {
+                        writeln!(self.writer, "{:>4}  {}", i + 1, entry)?;
+                    }
+                    continue;
+                },
+                "!!" => self.history.last().cloned(),
+                command if command.starts_with('!') => command[1 ..].parse::<usize>().ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|n| self.history.get(n).cloned()),
+                _ => Some(line.to_string()),
+            };
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    writeln!(self.writer, "No such input in the history")?;
+                    continue;
+                },
+            };
+
+            match parse_input(&line) {
+                Ok(input) => {
+                    self.history.push(line);
+                    return Ok(Some(input));
+                },
+                Err(message) => writeln!(self.writer, "{}", message)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Tape;
+
+    fn run(tape: &str, lines: &str) -> (String, Computer, Vec<String>) {
+        let tape: Tape = tape.parse().unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        let mut console = Console::new(lines.as_bytes(), Vec::new());
+        console.run(&mut computer).unwrap();
+        (String::from_utf8(console.writer().clone()).unwrap(), computer, console.history)
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(Ok(vec![1, -2, 3]), parse_input("1, -2 3"));
+        assert_eq!(Ok(vec![104, 105, 10]), parse_input("hi"));
+        assert_eq!(Ok(vec![49, 50, 10]), parse_input("\"12"));
+        assert_eq!(Ok(vec![10]), parse_input(""));
+        assert_eq!(Err("é isn't ASCII".to_string()), parse_input("café"));
+    }
+
+    #[test]
+    fn test_describe_output() {
+        assert_eq!("0,0,1234", describe_output(&[0, 0, 1234]));
+        assert_eq!("ok\n", describe_output(&[111, 107, 10]));
+    }
+
+    #[test]
+    fn test_day_5() {
+        let tape = Tape::load("../day-05/input.txt").unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        let mut console = Console::new("1\n".as_bytes(), Vec::new());
+        assert_eq!(CPUState::Halted, console.run(&mut computer).unwrap());
+
+        let shown = String::from_utf8(console.writer().clone()).unwrap();
+        assert_eq!(Some("[0] input> 0,0,0,0,0,0,0,0,0,12234644"), shown.lines().next());
+    }
+
+    #[test]
+    fn test_prompts() {
+        // Echoes inputs until given 0
+        let echo = "3,100,4,100,1005,100,0,99";
+        let (shown, computer, history) = run(echo, "5\n:history\n!!\n!3\n!1\nx\n0\n");
+        assert_eq!(vec![5, 5, 5, 120, 10, 0], computer.io.output);
+        assert_eq!(vec!["5", "5", "5", "x", "0"], history);
+        assert_eq!("\
+[0] input> 5
+[0] input>    1  5
+[0] input> 5
+[0] input> No such input in the history
+[0] input> 5
+[0] input> x
+[0] input> 0
+", shown);
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        let (shown, computer, _) = run("3,100,99", "");
+        assert_eq!(CPUState::AwaitingInput, computer.cpu_state());
+        assert_eq!("[0] input> \n", shown);
+
+        let (_, computer, _) = run("3,100,99", ":quit\n1\n");
+        assert_eq!(CPUState::AwaitingInput, computer.cpu_state());
+    }
+}
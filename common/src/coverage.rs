@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::computer::{Access, Reference, Tape};
+use crate::disassembler::{disassemble_with_hints, Line};
+
+/// How often a single memory location has been touched
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct CellAccess {
+    pub fetches: usize,
+    pub operand_reads: usize,
+    pub reads: usize,
+    pub writes: usize,
+}
+
+impl CellAccess {
+    /// True if the location was ever part of an executed instruction
+    pub fn executed(&self) -> bool {
+        self.fetches > 0 || self.operand_reads > 0
+    }
+
+    /// True if the location was ever read or written as data
+    pub fn used_as_data(&self) -> bool {
+        self.reads > 0 || self.writes > 0
+    }
+}
+
+/// Records how the CPU has accessed each memory location. Enable it with
+/// `Computer::enable_coverage`.
+///
+/// ```
+/// use common::computer::Computer;
+///
+/// let mut computer = Computer::new_with_tape(&"1002,4,3,4,33".parse().unwrap());
+/// computer.enable_coverage();
+/// computer.run();
+///
+/// let coverage = computer.memory.coverage.as_ref().unwrap();
+/// assert_eq!(1, coverage.cell(0).fetches);
+/// assert_eq!(1, coverage.cell(4).writes);
+/// assert_eq!(1, coverage.cell(4).fetches);
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct Coverage {
+    cells: Vec<CellAccess>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage { cells: Vec::new() }
+    }
+
+    /// Returns the accesses recorded for the passed location
+    pub fn cell(&self, location: Reference) -> CellAccess {
+        if location < 0 || location as usize >= self.cells.len() {
+            return CellAccess::default();
+        }
+        self.cells[location as usize]
+    }
+
+    pub(crate) fn record(&mut self, access: Access, location: Reference) {
+        if location < 0 {
+            return;
+        }
+        let index = location as usize;
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, CellAccess::default());
+        }
+
+        let cell = &mut self.cells[index];
+        match access {
+            Access::Fetch => cell.fetches += 1,
+            Access::Operand => cell.operand_reads += 1,
+            Access::Read => cell.reads += 1,
+            Access::Write => cell.writes += 1,
+        }
+    }
+
+    /// Builds a report overlaying this coverage on a disassembly of the
+    /// passed tape. Locations which were executed are used to keep the
+    /// disassembly aligned with the real instruction stream.
+    pub fn report(&self, tape: &Tape) -> CoverageReport {
+        let lines = disassemble_with_hints(&tape.contents, |a| self.cell(a).fetches > 0);
+        let lines = lines.into_iter()
+            .map(|line| (self.classify(&line), line))
+            .collect();
+
+        CoverageReport { lines }
+    }
+
+    fn classify(&self, line: &Line) -> LineCoverage {
+        let cells: Vec<CellAccess> = (0 .. line.len())
+            .map(|i| self.cell(line.address + i as Reference))
+            .collect();
+
+        if cells[0].fetches > 0 {
+            if cells.iter().any(|c| c.writes > 0) {
+                LineCoverage::SelfModified
+            } else {
+                LineCoverage::Executed
+            }
+        } else if cells.iter().any(|c| c.used_as_data()) {
+            LineCoverage::DataOnly
+        } else {
+            LineCoverage::NeverExecuted
+        }
+    }
+}
+
+/// The coverage of a single line in a `CoverageReport`
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LineCoverage {
+    /// The instruction was executed and never written to
+    Executed,
+    /// Nothing touched the line
+    NeverExecuted,
+    /// The line was read or written as data but never executed
+    DataOnly,
+    /// The instruction was executed and also written to
+    SelfModified,
+}
+
+impl LineCoverage {
+    fn executed(self) -> bool {
+        self == LineCoverage::Executed || self == LineCoverage::SelfModified
+    }
+
+    fn marker(self) -> char {
+        match self {
+            LineCoverage::Executed => 'X',
+            LineCoverage::NeverExecuted => '-',
+            LineCoverage::DataOnly => 'D',
+            LineCoverage::SelfModified => 'M',
+        }
+    }
+}
+
+/// A disassembly listing annotated with coverage
+///
+/// ```
+/// use common::computer::{Computer, Tape};
+/// use common::coverage::LineCoverage;
+///
+/// let tape: Tape = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1".parse().unwrap();
+/// let mut computer = Computer::new_with_tape(&tape);
+/// computer.enable_coverage();
+/// computer.io.add_input(5);
+/// computer.run();
+///
+/// let report = computer.memory.coverage.as_ref().unwrap().report(&tape);
+/// assert_eq!(LineCoverage::SelfModified, report.lines[1].0);
+/// assert_eq!(LineCoverage::NeverExecuted, report.lines[2].0);
+/// ```
+pub struct CoverageReport {
+    pub lines: Vec<(LineCoverage, Line)>,
+}
+
+impl CoverageReport {
+    /// The number of instructions in the listing, including data which
+    /// only became an instruction by being overwritten
+    pub fn instruction_count(&self) -> usize {
+        self.lines.iter().filter(|(c, l)| l.is_instruction || c.executed()).count()
+    }
+
+    /// The number of instructions in the listing which were executed
+    pub fn executed_count(&self) -> usize {
+        self.lines.iter().filter(|(c, _)| c.executed()).count()
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (coverage, line) in &self.lines {
+            writeln!(f, "{} {}", coverage.marker(), line)?;
+        }
+        write!(f, "Executed {} of {} instructions", self.executed_count(), self.instruction_count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Computer;
+
+    #[test]
+    fn test_coverage_accumulates_over_runs() {
+        let tape: Tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let mut computer = Computer::new();
+        computer.enable_coverage();
+
+        computer.reset_and_load_tape(&tape);
+        computer.io.add_input(1);
+        computer.run();
+
+        computer.reset_and_load_tape(&tape);
+        computer.io.add_input(8);
+        computer.run();
+
+        let coverage = computer.memory.coverage.as_ref().unwrap();
+        assert_eq!(2, coverage.cell(0).fetches);
+        assert_eq!(2, coverage.cell(1).operand_reads);
+        assert_eq!(4, coverage.cell(9).writes);
+        assert_eq!(2, coverage.cell(10).reads);
+    }
+
+    #[test]
+    fn test_report_branches() {
+        let tape: Tape = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9".parse().unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        computer.enable_coverage();
+        computer.io.add_input(0);
+        computer.run();
+
+        let report = computer.memory.coverage.as_ref().unwrap().report(&tape);
+        let coverage: Vec<LineCoverage> = report.lines.iter().map(|(c, _)| *c).collect();
+        assert_eq!(vec![
+            LineCoverage::Executed,         // IN [12]
+            LineCoverage::Executed,         // JZ [12], [15]
+            LineCoverage::NeverExecuted,    // ADD [13], [14], [13]
+            LineCoverage::Executed,         // OUT [13]
+            LineCoverage::Executed,         // HLT
+            LineCoverage::DataOnly,         // -1
+            LineCoverage::DataOnly,         // 0
+            LineCoverage::NeverExecuted,    // 1
+            LineCoverage::DataOnly,         // 9
+        ], coverage);
+        assert_eq!(4, report.executed_count());
+        assert_eq!(5, report.instruction_count());
+    }
+
+    #[test]
+    fn test_report_display() {
+        let tape: Tape = "1002,4,3,4,33".parse().unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        computer.enable_coverage();
+        computer.run();
+
+        let report = computer.memory.coverage.as_ref().unwrap().report(&tape);
+        let text = report.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("X 00000"));
+        assert!(lines[1].starts_with("M 00004"));
+        assert_eq!("Executed 2 of 2 instructions", lines[2]);
+    }
+}
@@ -0,0 +1,990 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::computer::{DecodedInstruction, OpCode, ParameterMode, Reference, Word};
+use crate::instruction_set::InstructionSet;
+
+/// Something an instruction reads or writes
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Value {
+    Number(Word),
+    /// A fixed location
+    Memory(Reference),
+    /// A location in the function's stack frame, as an offset from the
+    /// relative base when the function was called
+    Frame(Reference),
+    /// A location relative to a relative base which couldn't be followed
+    Relative(Reference),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Memory(address) => write!(f, "mem[{}]", address),
+            Value::Frame(offset) => write!(f, "frame[{}]", offset),
+            Value::Relative(offset) if *offset < 0 => write!(f, "mem[rb{}]", offset),
+            Value::Relative(offset) => write!(f, "mem[rb+{}]", offset),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Expr {
+    Value(Value),
+    Add(Value, Value),
+    Mul(Value, Value),
+    LessThan(Value, Value),
+    Equal(Value, Value),
+    Input,
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Value(v) => write!(f, "{}", v),
+            Expr::Add(v, Value::Number(0)) | Expr::Add(Value::Number(0), v) => write!(f, "{}", v),
+            Expr::Add(a, Value::Number(n)) if n < 0 => write!(f, "{} - {}", a, n.unsigned_abs()),
+            Expr::Add(a, b) => write!(f, "{} + {}", a, b),
+            Expr::Mul(v, Value::Number(1)) | Expr::Mul(Value::Number(1), v) => write!(f, "{}", v),
+            Expr::Mul(v, Value::Number(-1)) | Expr::Mul(Value::Number(-1), v) => write!(f, "-{}", v),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::LessThan(a, b) => write!(f, "{} < {}", a, b),
+            Expr::Equal(a, b) => write!(f, "{} == {}", a, b),
+            Expr::Input => write!(f, "input()"),
+        }
+    }
+}
+
+/// A test of whether a value is zero
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Condition {
+    pub value: Value,
+    /// True if the condition holds when the value isn't zero
+    pub nonzero: bool,
+}
+
+impl Condition {
+    pub fn negate(self) -> Condition {
+        Condition { value: self.value, nonzero: !self.nonzero }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} 0", self.value, if self.nonzero { "!=" } else { "==" })
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Statement {
+    Assign(Value, Expr),
+    Output(Value),
+    /// Moves the relative base where it can't be followed
+    AdjustBase(Value),
+    /// Calls the function at `target`, whose frame starts at `frame`
+    Call { target: Reference, frame: Value },
+    If(Condition, Vec<Statement>, Vec<Statement>),
+    Loop(Vec<Statement>),
+    While(Condition, Vec<Statement>),
+    Break,
+    Continue,
+    /// Marks the target of a `Goto`
+    Label(Reference),
+    Goto(Reference),
+    /// A jump to an address the program works out
+    GotoValue(Value),
+    Return,
+    Halt,
+    /// Execution runs into something which isn't an instruction
+    Invalid(Reference),
+}
+
+impl Statement {
+    /// Whether control never carries on to the next statement
+    fn is_jump(&self) -> bool {
+        matches!(self,
+            Statement::Break | Statement::Continue | Statement::Goto(_) | Statement::GotoValue(_)
+            | Statement::Return | Statement::Halt | Statement::Invalid(_))
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent);
+        let block = |f: &mut fmt::Formatter, statements: &[Statement]| -> fmt::Result {
+            for statement in statements {
+                statement.write(f, indent + 1)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            Statement::Assign(to, value) => writeln!(f, "{}{} = {}", pad, to, value),
+            Statement::Output(value) => writeln!(f, "{}output({})", pad, value),
+            Statement::AdjustBase(value) => writeln!(f, "{}rb += {}", pad, value),
+            Statement::Call { target, frame } => writeln!(f, "{}call f_{} (frame at {})", pad, target, frame),
+            Statement::If(condition, then, otherwise) => {
+                writeln!(f, "{}if {} {{", pad, condition)?;
+                block(f, then)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{}}} else {{", pad)?;
+                    block(f, otherwise)?;
+                }
+                writeln!(f, "{}}}", pad)
+            },
+            Statement::Loop(body) => {
+                writeln!(f, "{}loop {{", pad)?;
+                block(f, body)?;
+                writeln!(f, "{}}}", pad)
+            },
+            Statement::While(condition, body) => {
+                writeln!(f, "{}while {} {{", pad, condition)?;
+                block(f, body)?;
+                writeln!(f, "{}}}", pad)
+            },
+            Statement::Break => writeln!(f, "{}break", pad),
+            Statement::Continue => writeln!(f, "{}continue", pad),
+            Statement::Label(address) => writeln!(f, "{}L{}:", pad, address),
+            Statement::Goto(address) => writeln!(f, "{}goto L{}", pad, address),
+            Statement::GotoValue(value) => writeln!(f, "{}goto {}", pad, value),
+            Statement::Return => writeln!(f, "{}return", pad),
+            Statement::Halt => writeln!(f, "{}halt", pad),
+            Statement::Invalid(address) => writeln!(f, "{}invalid instruction at {}", pad, address),
+        }
+    }
+}
+
+/// The code reachable from an entry point without following calls
+#[derive(PartialEq, Clone, Debug)]
+pub struct Function {
+    pub entry: Reference,
+    pub body: Vec<Statement>,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "fn f_{}() {{", self.entry)?;
+        for statement in &self.body {
+            statement.write(f, 1)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// A whole program as pseudocode, one function per entry point in
+/// address order, starting with the one at 0
+#[derive(PartialEq, Clone, Debug)]
+pub struct Decompiled {
+    pub functions: Vec<Function>,
+}
+
+impl Decompiled {
+    pub fn function(&self, entry: Reference) -> Option<&Function> {
+        self.functions.iter().find(|f| f.entry == entry)
+    }
+}
+
+impl fmt::Display for Decompiled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decompiles memory into structured pseudocode.
+///
+/// The program is followed from location 0 to find its control flow
+/// graph, which is turned back into `if`/`else`, loops, `break` and
+/// `continue`, with `goto` used for anything which doesn't fit.
+///
+/// Calls are recognised by the usual relative base calling convention:
+/// the caller stores the address following a jump into its stack frame
+/// with a relative write, possibly moves the relative base with `109,N`,
+/// then jumps. A jump through a relative location, like `2105,1,0`, is a
+/// return. Moves of the relative base by constants are followed within
+/// each function, so frame locations get stable names, and are only
+/// shown where that isn't possible.
+///
+/// ```
+/// use common::decompiler::decompile;
+///
+/// let decompiled = decompile(&[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]);
+/// assert_eq!("\
+/// fn f_0() {
+///     mem[12] = input()
+///     if mem[12] != 0 {
+///         mem[13] = mem[13] + mem[14]
+///     }
+///     output(mem[13])
+///     halt
+/// }
+/// ", decompiled.to_string());
+/// ```
+pub fn decompile(memory: &[Word]) -> Decompiled {
+    let mut explorer = Explorer { memory, instruction_set: InstructionSet::intcode(), written: HashSet::new() };
+
+    // Jumps through locations the program writes aren't followed, and
+    // finding what it writes means finding its code, so repeat until the
+    // two agree
+    let functions = loop {
+        let functions = explorer.functions();
+        let written: HashSet<Reference> = functions.values()
+            .flat_map(|blocks| blocks.values())
+            .flat_map(|block| block.instructions.iter())
+            .flat_map(|instruction| instruction.writes())
+            .collect();
+        if written == explorer.written {
+            break functions;
+        }
+        explorer.written = written;
+    };
+
+    Decompiled {
+        functions: functions.iter().map(|(entry, blocks)| Function { entry: *entry, body: structure(*entry, blocks) }).collect(),
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Instruction {
+    address: Reference,
+    opcode: OpCode,
+    modes: Vec<ParameterMode>,
+    parameters: Vec<Word>,
+}
+
+impl Instruction {
+    fn next(&self) -> Reference {
+        self.address + 1 + self.parameters.len() as Reference
+    }
+
+    /// The fixed locations the instruction writes to
+    fn writes(&self) -> Option<Reference> {
+        let parameter = match self.opcode {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equal => 2,
+            OpCode::ConsumeInput => 0,
+            _ => return None,
+        };
+        match self.modes[parameter] {
+            ParameterMode::Position => Some(self.parameters[parameter]),
+            ParameterMode::Immediate => Some(self.address + 1 + parameter as Reference),
+            ParameterMode::Relative => None,
+        }
+    }
+
+    /// The value a relative write stores, if it's a constant which
+    /// doesn't overflow
+    fn stored_constant(&self) -> Option<Word> {
+        let constant = |i: usize| Some(self.parameters[i]).filter(|_| self.modes[i] == ParameterMode::Immediate);
+        if self.modes.get(2) != Some(&ParameterMode::Relative) {
+            return None;
+        }
+        match self.opcode {
+            OpCode::Add => constant(0)?.checked_add(constant(1)?),
+            OpCode::Mul => constant(0)?.checked_mul(constant(1)?),
+            _ => None,
+        }
+    }
+}
+
+/// How control leaves a block
+#[derive(Clone, Debug)]
+enum Exit {
+    Next(Reference),
+    /// The last instruction jumps to `target` depending on its first
+    /// parameter, otherwise goes on to `next`
+    Branch { target: Reference, next: Reference },
+    /// Calls `target`, which comes back to `next`
+    Call { target: Reference, next: Reference },
+    /// Jumps through a relative location, going on to `next` if the jump
+    /// is conditional
+    Return { next: Option<Reference> },
+    /// Jumps through a location the program writes
+    Dynamic { next: Option<Reference> },
+    Halt,
+    Invalid(Reference),
+}
+
+#[derive(Clone, Debug)]
+struct Block {
+    instructions: Vec<Instruction>,
+    exit: Exit,
+}
+
+impl Block {
+    fn successors(&self) -> Vec<Reference> {
+        match self.exit {
+            Exit::Next(next) | Exit::Call { next, .. } => vec![next],
+            Exit::Branch { target, next } => vec![target, next],
+            Exit::Return { next } | Exit::Dynamic { next } => next.into_iter().collect(),
+            Exit::Halt | Exit::Invalid(_) => vec![],
+        }
+    }
+}
+
+type Blocks = BTreeMap<Reference, Block>;
+
+struct Explorer<'a> {
+    memory: &'a [Word],
+    instruction_set: InstructionSet,
+    /// Locations the program is known to write
+    written: HashSet<Reference>,
+}
+
+impl<'a> Explorer<'a> {
+    fn decode(&self, address: Reference) -> Option<Instruction> {
+        let word = *self.memory.get(usize::try_from(address).ok()?)?;
+        let decoded = DecodedInstruction::from_word(word, &self.instruction_set)?;
+        let opcode = decoded.opcode()?;
+        let count = decoded.len() - 1;
+        let parameters = self.memory.get(address as usize + 1 ..= address as usize + count)?.to_vec();
+        let modes = (0 .. count).map(|i| decoded.mode(i)).collect();
+        Some(Instruction { address, opcode, modes, parameters })
+    }
+
+    /// Where a jump goes, or `None` if it goes through a relative or
+    /// written location
+    fn jump_target(&self, jump: &Instruction) -> Option<Reference> {
+        match jump.modes[1] {
+            ParameterMode::Immediate => Some(jump.parameters[1]),
+            ParameterMode::Position if !self.written.contains(&jump.parameters[1]) => {
+                Some(self.memory.get(usize::try_from(jump.parameters[1]).ok()?).cloned().unwrap_or(0))
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether a jump is always or never taken, if that's known
+    fn jump_taken(&self, jump: &Instruction) -> Option<bool> {
+        if jump.modes[0] != ParameterMode::Immediate {
+            return None;
+        }
+        Some((jump.parameters[0] != 0) == (jump.opcode == OpCode::JumpIfNotZero))
+    }
+
+    /// Every function reachable from location 0, along with its blocks
+    fn functions(&self) -> BTreeMap<Reference, Blocks> {
+        let mut functions = BTreeMap::new();
+        let mut pending = vec![0];
+        while let Some(entry) = pending.pop() {
+            if functions.contains_key(&entry) {
+                continue;
+            }
+            let (blocks, calls) = self.explore(entry);
+            pending.extend(calls);
+            functions.insert(entry, blocks);
+        }
+        functions
+    }
+
+    /// Finds the blocks of the function starting at `entry`, and the
+    /// functions it calls
+    fn explore(&self, entry: Reference) -> (Blocks, Vec<Reference>) {
+        let mut instructions = BTreeMap::new();
+        let mut exits = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut calls = Vec::new();
+        let mut pending = vec![entry];
+        leaders.insert(entry);
+
+        while let Some(start) = pending.pop() {
+            let mut address = start;
+            let mut recent: Vec<Instruction> = Vec::new();
+
+            while !instructions.contains_key(&address) {
+                let instruction = match self.decode(address) {
+                    Some(instruction) => instruction,
+                    None => break,
+                };
+                let next = instruction.next();
+                instructions.insert(address, instruction.clone());
+
+                let exit = match instruction.opcode {
+                    OpCode::Halt => Some(Exit::Halt),
+                    OpCode::JumpIfNotZero | OpCode::JumpIfZero => {
+                        let taken = self.jump_taken(&instruction);
+                        let conditional = |exit: Option<Reference>| if taken == Some(true) { None } else { exit };
+                        match (taken, self.jump_target(&instruction)) {
+                            (Some(false), _) => None,
+                            (_, None) if instruction.modes[1] == ParameterMode::Relative => Some(Exit::Return { next: conditional(Some(next)) }),
+                            (_, None) => Some(Exit::Dynamic { next: conditional(Some(next)) }),
+                            (Some(true), Some(target)) if is_call(&recent, next) => {
+                                calls.push(target);
+                                Some(Exit::Call { target, next })
+                            },
+                            (Some(true), Some(target)) => Some(Exit::Next(target)),
+                            (None, Some(target)) => Some(Exit::Branch { target, next }),
+                        }
+                    },
+                    _ => None,
+                };
+                recent.push(instruction);
+
+                if let Some(exit) = exit {
+                    let successors = Block { instructions: Vec::new(), exit: exit.clone() }.successors();
+                    leaders.extend(&successors);
+                    pending.extend(successors);
+                    if let Exit::Branch { next, .. } | Exit::Return { next: Some(next) } | Exit::Dynamic { next: Some(next) } = exit {
+                        leaders.insert(next);
+                    }
+                    exits.insert(address, exit);
+                    break;
+                }
+                address = next;
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for start in &leaders {
+            let mut block = Block { instructions: Vec::new(), exit: Exit::Invalid(*start) };
+            let mut address = *start;
+            while let Some(instruction) = instructions.get(&address) {
+                block.instructions.push(instruction.clone());
+                if let Some(exit) = exits.get(&address) {
+                    block.exit = exit.clone();
+                    break;
+                }
+                address = instruction.next();
+                block.exit = if leaders.contains(&address) { Exit::Next(address) } else { Exit::Invalid(address) };
+                if leaders.contains(&address) {
+                    break;
+                }
+            }
+            blocks.insert(*start, block);
+        }
+        (blocks, calls)
+    }
+}
+
+/// The last instruction before a jump, other than moves of the relative
+/// base, which would be the one storing the return address for a call
+fn return_address_store(before: &[Instruction]) -> Option<&Instruction> {
+    before.iter().rev().find(|i| !(i.opcode == OpCode::AdjustRelativeBase && i.modes[0] == ParameterMode::Immediate))
+}
+
+/// Whether the instructions leading up to a jump store the address the
+/// jump returns to in the stack frame
+fn is_call(recent: &[Instruction], next: Reference) -> bool {
+    return_address_store(recent).and_then(|i| i.stored_constant()) == Some(next)
+}
+
+/// Where the relative base is known to be
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Base {
+    Absolute(Reference),
+    /// An offset from the base when the function was called
+    Entry(Reference),
+    Unknown,
+}
+
+impl Base {
+    fn adjust(self, instruction: &Instruction) -> Base {
+        if instruction.opcode != OpCode::AdjustRelativeBase {
+            return self;
+        }
+        match (self, instruction.modes[0], instruction.parameters[0]) {
+            (Base::Absolute(base), ParameterMode::Immediate, n) => base.checked_add(n).map_or(Base::Unknown, Base::Absolute),
+            (Base::Entry(base), ParameterMode::Immediate, n) => base.checked_add(n).map_or(Base::Unknown, Base::Entry),
+            _ => Base::Unknown,
+        }
+    }
+
+    fn value(self, instruction: &Instruction, parameter: usize) -> Value {
+        let word = instruction.parameters[parameter];
+        match (instruction.modes[parameter], self) {
+            (ParameterMode::Position, _) => Value::Memory(word),
+            (ParameterMode::Immediate, _) => Value::Number(word),
+            (ParameterMode::Relative, Base::Absolute(base)) => base.checked_add(word).map_or(Value::Relative(word), Value::Memory),
+            (ParameterMode::Relative, Base::Entry(base)) => base.checked_add(word).map_or(Value::Relative(word), Value::Frame),
+            (ParameterMode::Relative, Base::Unknown) => Value::Relative(word),
+        }
+    }
+
+    /// The location the relative base points at
+    fn frame(self) -> Value {
+        match self {
+            Base::Absolute(base) => Value::Memory(base),
+            Base::Entry(base) => Value::Frame(base),
+            Base::Unknown => Value::Relative(0),
+        }
+    }
+
+    /// The location written by a parameter, which is the parameter itself
+    /// in immediate mode
+    fn target(self, instruction: &Instruction, parameter: usize) -> Value {
+        match instruction.modes[parameter] {
+            ParameterMode::Immediate => Value::Memory(instruction.address + 1 + parameter as Reference),
+            _ => self.value(instruction, parameter),
+        }
+    }
+}
+
+/// Follows the relative base through a function's blocks
+fn bases(entry: Reference, blocks: &Blocks) -> BTreeMap<Reference, Base> {
+    let mut bases = BTreeMap::new();
+    bases.insert(entry, if entry == 0 { Base::Absolute(0) } else { Base::Entry(0) });
+    let mut pending = vec![entry];
+
+    while let Some(start) = pending.pop() {
+        let block = &blocks[&start];
+        let end = block.instructions.iter().fold(bases[&start], |base, i| base.adjust(i));
+        for successor in block.successors() {
+            let merged = match bases.get(&successor) {
+                None => end,
+                Some(base) if *base == end => continue,
+                Some(_) => Base::Unknown,
+            };
+            if bases.get(&successor) != Some(&merged) {
+                bases.insert(successor, merged);
+                pending.push(successor);
+            }
+        }
+    }
+    bases
+}
+
+/// The blocks each block is always reached through, itself included
+fn dominators(entry: Reference, blocks: &Blocks) -> BTreeMap<Reference, BTreeSet<Reference>> {
+    let mut predecessors: BTreeMap<Reference, Vec<Reference>> = BTreeMap::new();
+    for (start, block) in blocks {
+        for successor in block.successors() {
+            predecessors.entry(successor).or_default().push(*start);
+        }
+    }
+    let everything: BTreeSet<Reference> = blocks.keys().cloned().collect();
+    solve(entry, &everything, |node| predecessors.get(&node).cloned().unwrap_or_default())
+}
+
+/// A virtual block which every block with no successors goes on to
+const EXIT: Reference = Reference::MIN;
+
+/// The blocks every path from each block to the end of the function
+/// goes through, itself included. Blocks which never reach the end
+/// aren't included.
+fn post_dominators(blocks: &Blocks) -> BTreeMap<Reference, BTreeSet<Reference>> {
+    let successors = |node: Reference| -> Vec<Reference> {
+        if node == EXIT {
+            return vec![];
+        }
+        let successors = blocks[&node].successors();
+        if successors.is_empty() { vec![EXIT] } else { successors }
+    };
+
+    // Only blocks which can reach the end have post dominators
+    let mut reaches: BTreeSet<Reference> = BTreeSet::new();
+    reaches.insert(EXIT);
+    loop {
+        let before = reaches.len();
+        for node in blocks.keys() {
+            if successors(*node).iter().any(|s| reaches.contains(s)) {
+                reaches.insert(*node);
+            }
+        }
+        if reaches.len() == before {
+            break;
+        }
+    }
+
+    solve(EXIT, &reaches, |node| successors(node).into_iter().filter(|s| reaches.contains(s)).collect())
+}
+
+/// Solves the dominator equations over `nodes`, where `before` gives the
+/// nodes leading into a node
+fn solve<F>(root: Reference, nodes: &BTreeSet<Reference>, before: F) -> BTreeMap<Reference, BTreeSet<Reference>>
+    where F: Fn(Reference) -> Vec<Reference>
+{
+    let mut result: BTreeMap<Reference, BTreeSet<Reference>> = nodes.iter().map(|n| (*n, nodes.clone())).collect();
+    result.insert(root, [root].iter().cloned().collect());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in nodes.iter().filter(|n| **n != root) {
+            let mut set = before(*node).iter()
+                .filter_map(|p| result.get(p))
+                .fold(None, |acc: Option<BTreeSet<Reference>>, s| Some(match acc {
+                    None => s.clone(),
+                    Some(acc) => acc.intersection(s).cloned().collect(),
+                }))
+                .unwrap_or_default();
+            set.insert(*node);
+            if set != result[node] {
+                result.insert(*node, set);
+                changed = true;
+            }
+        }
+    }
+    result
+}
+
+struct Loop {
+    body: BTreeSet<Reference>,
+    exit: Option<Reference>,
+}
+
+fn find_loops(blocks: &Blocks, dominators: &BTreeMap<Reference, BTreeSet<Reference>>, join: &BTreeMap<Reference, Reference>) -> BTreeMap<Reference, Loop> {
+    let mut loops: BTreeMap<Reference, Loop> = BTreeMap::new();
+
+    for (start, block) in blocks {
+        for header in block.successors() {
+            if !dominators[start].contains(&header) {
+                continue;
+            }
+            // A back edge, so everything which reaches it without going
+            // through the header is in the loop
+            let lp = loops.entry(header).or_insert_with(|| Loop { body: [header].iter().cloned().collect(), exit: None });
+            let mut pending = vec![*start];
+            while let Some(node) = pending.pop() {
+                if lp.body.insert(node) {
+                    pending.extend(blocks.iter().filter(|(_, b)| b.successors().contains(&node)).map(|(s, _)| *s));
+                }
+            }
+        }
+    }
+
+    for (header, lp) in loops.iter_mut() {
+        let exits: BTreeSet<Reference> = lp.body.iter()
+            .flat_map(|b| blocks[b].successors())
+            .filter(|s| !lp.body.contains(s))
+            .collect();
+        lp.exit = join.get(header).filter(|j| exits.contains(j)).or_else(|| exits.iter().next()).cloned();
+    }
+    loops
+}
+
+struct Structurer<'a> {
+    blocks: &'a Blocks,
+    bases: BTreeMap<Reference, Base>,
+    loops: BTreeMap<Reference, Loop>,
+    /// The first block every path from a branch goes on to
+    join: BTreeMap<Reference, Reference>,
+    emitted: HashSet<Reference>,
+    /// Blocks which need a label
+    labels: BTreeSet<Reference>,
+    gotos: BTreeSet<Reference>,
+}
+
+fn structure(entry: Reference, blocks: &Blocks) -> Vec<Statement> {
+    let dominators = dominators(entry, blocks);
+    let post_dominators = post_dominators(blocks);
+
+    // The immediate post dominator is the one with just one fewer post
+    // dominators of its own
+    let join = post_dominators.iter()
+        .filter_map(|(node, set)| {
+            let join = set.iter().find(|p| **p != *node && post_dominators[p].len() == set.len() - 1)?;
+            Some((*node, *join)).filter(|(_, j)| *j != EXIT)
+        })
+        .collect();
+    let loops = find_loops(blocks, &dominators, &join);
+
+    let mut structurer = Structurer {
+        blocks,
+        bases: bases(entry, blocks),
+        loops,
+        join,
+        emitted: HashSet::new(),
+        labels: BTreeSet::new(),
+        gotos: BTreeSet::new(),
+    };
+
+    // Labels are only known once every goto has been found
+    let mut body = structurer.region(entry, None, &[], false);
+    if !structurer.gotos.is_empty() {
+        structurer.labels = std::mem::take(&mut structurer.gotos);
+        structurer.emitted.clear();
+        body = structurer.region(entry, None, &[], false);
+    }
+    body
+}
+
+impl<'a> Structurer<'a> {
+    /// Turns the blocks from `start` into statements, stopping at `stop`.
+    /// `loops` holds the headers of the enclosing loops, innermost last,
+    /// and `entering` is set when `start` is the header of the innermost.
+    fn region(&mut self, start: Reference, stop: Option<Reference>, loops: &[Reference], mut entering: bool) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        let mut current = Some(start);
+
+        while let Some(address) = current {
+            if Some(address) == stop {
+                break;
+            }
+            if let Some(header) = loops.last() {
+                if address == *header && !entering {
+                    statements.push(Statement::Continue);
+                    break;
+                }
+                if self.loops[header].exit == Some(address) {
+                    statements.push(Statement::Break);
+                    break;
+                }
+            }
+            let outer = loops.iter().any(|h| (*h == address && !entering) || self.loops[h].exit == Some(address));
+            if outer || (self.emitted.contains(&address) && !entering) {
+                self.gotos.insert(address);
+                statements.push(Statement::Goto(address));
+                break;
+            }
+            if self.loops.contains_key(&address) && !entering {
+                let mut inner = loops.to_vec();
+                inner.push(address);
+                let body = self.region(address, None, &inner, true);
+                statements.push(make_loop(body));
+                current = self.loops[&address].exit;
+                continue;
+            }
+            entering = false;
+
+            self.emitted.insert(address);
+            if self.labels.contains(&address) {
+                statements.push(Statement::Label(address));
+            }
+            current = self.block(address, stop, loops, &mut statements);
+        }
+        statements
+    }
+
+    /// Adds the statements for one block, returning the block to carry on
+    /// with
+    fn block(&mut self, address: Reference, stop: Option<Reference>, loops: &[Reference], statements: &mut Vec<Statement>) -> Option<Reference> {
+        let block = &self.blocks[&address];
+        let mut base = self.bases[&address];
+
+        // The return address is part of the call
+        let store = match block.exit {
+            Exit::Call { .. } => return_address_store(&block.instructions[.. block.instructions.len() - 1]).map(|i| i.address),
+            _ => None,
+        };
+
+        for instruction in block.instructions.iter().filter(|i| Some(i.address) != store) {
+            let value = |i| base.value(instruction, i);
+            let target = |i| base.target(instruction, i);
+            match instruction.opcode {
+                OpCode::Add => statements.push(Statement::Assign(target(2), Expr::Add(value(0), value(1)))),
+                OpCode::Mul => statements.push(Statement::Assign(target(2), Expr::Mul(value(0), value(1)))),
+                OpCode::LessThan => statements.push(Statement::Assign(target(2), Expr::LessThan(value(0), value(1)))),
+                OpCode::Equal => statements.push(Statement::Assign(target(2), Expr::Equal(value(0), value(1)))),
+                OpCode::ConsumeInput => statements.push(Statement::Assign(target(0), Expr::Input)),
+                OpCode::ProduceOutput => statements.push(Statement::Output(value(0))),
+                OpCode::AdjustRelativeBase => {
+                    let adjusted = base.adjust(instruction);
+                    if adjusted == Base::Unknown {
+                        statements.push(Statement::AdjustBase(value(0)));
+                    }
+                    base = adjusted;
+                },
+                OpCode::JumpIfNotZero | OpCode::JumpIfZero | OpCode::Halt => {},
+            }
+        }
+
+        let last = block.instructions.last();
+        let condition = || {
+            let jump = last.unwrap();
+            Condition { value: base.value(jump, 0), nonzero: jump.opcode == OpCode::JumpIfNotZero }
+        };
+        // A conditional exit which might be taken
+        let guarded = |statements: &mut Vec<Statement>, statement: Statement, next: Option<Reference>| {
+            match next {
+                Some(_) => statements.push(Statement::If(condition(), vec![statement], Vec::new())),
+                None => statements.push(statement),
+            }
+            next
+        };
+
+        match block.exit {
+            Exit::Next(next) => Some(next),
+            Exit::Call { target, next } => {
+                statements.push(Statement::Call { target, frame: base.frame() });
+                Some(next)
+            },
+            Exit::Branch { target, next } => {
+                let condition = condition();
+                let join = self.join.get(&address).cloned()
+                    .filter(|j| loops.last().is_none_or(|h| self.loops[h].body.contains(j)));
+                let until = join.or(stop);
+                let then = self.region(target, until, loops, false);
+                let otherwise = self.region(next, until, loops, false);
+                statements.extend(make_if(condition, then, otherwise));
+                join
+            },
+            Exit::Return { next } => guarded(statements, Statement::Return, next),
+            Exit::Dynamic { next } => {
+                let jump = last.unwrap();
+                guarded(statements, Statement::GotoValue(base.value(jump, 1)), next)
+            },
+            Exit::Halt => {
+                statements.push(Statement::Halt);
+                None
+            },
+            Exit::Invalid(address) => {
+                statements.push(Statement::Invalid(address));
+                None
+            },
+        }
+    }
+}
+
+/// Builds an `if`, leaving out empty branches and pulling the rest of the
+/// code out of an `else` when the `if` always jumps away
+fn make_if(condition: Condition, then: Vec<Statement>, otherwise: Vec<Statement>) -> Vec<Statement> {
+    let (condition, then, otherwise) = match (then.is_empty(), otherwise.is_empty()) {
+        (true, true) => return Vec::new(),
+        (true, false) => (condition.negate(), otherwise, then),
+        _ => (condition, then, otherwise),
+    };
+    if then.last().is_some_and(|s| s.is_jump()) {
+        let mut statements = vec![Statement::If(condition, then, Vec::new())];
+        statements.extend(otherwise);
+        return statements;
+    }
+    vec![Statement::If(condition, then, otherwise)]
+}
+
+/// Builds a loop, turning it into a `while` if it starts by testing
+/// whether to leave
+fn make_loop(mut body: Vec<Statement>) -> Statement {
+    if body.last() == Some(&Statement::Continue) {
+        body.pop();
+    }
+    match body.first() {
+        Some(Statement::If(condition, then, otherwise)) if *then == [Statement::Break] && otherwise.is_empty() => {
+            let condition = condition.negate();
+            body.remove(0);
+            Statement::While(condition, body)
+        },
+        _ => Statement::Loop(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    #[test]
+    fn test_straight_line() {
+        assert_eq!("\
+fn f_0() {
+    mem[9] = input()
+    mem[9] = mem[9] == mem[10]
+    output(mem[9])
+    halt
+}
+", decompile(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]).to_string());
+    }
+
+    #[test]
+    fn test_if_else() {
+        let tape = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+        let memory: Vec<Word> = tape.split(',').map(|w| w.parse().unwrap()).collect();
+        assert_eq!("\
+fn f_0() {
+    mem[21] = input()
+    mem[20] = mem[21] == 8
+    if mem[20] != 0 {
+        mem[20] = mem[21] * 125
+        output(mem[20])
+    } else {
+        mem[20] = 8 < mem[21]
+        if mem[20] == 0 {
+            output(999)
+        } else {
+            mem[20] = 1000 + 1
+            output(mem[20])
+        }
+    }
+    halt
+}
+", decompile(&memory).to_string());
+    }
+
+    #[test]
+    fn test_loops_and_calls() {
+        let tape = compile("
+            fn triangle(n) {
+                let total = 0;
+                while n > 0 {
+                    total = total + n;
+                    n = n - 1;
+                }
+                return total;
+            }
+            fn main() {
+                output(triangle(input()));
+            }").unwrap();
+        let decompiled = decompile(&tape.contents);
+
+        assert_eq!(3, decompiled.functions.len());
+        let text = decompiled.to_string();
+        assert!(!text.contains("goto"), "{}", text);
+        assert!(text.contains("call f_"), "{}", text);
+
+        let triangle = decompiled.functions[1].to_string();
+        assert!(triangle.contains("frame[2] = frame[2] + frame[1]"), "{}", triangle);
+        assert!(triangle.contains("    loop {\n"), "{}", triangle);
+        assert!(triangle.contains("            break\n"), "{}", triangle);
+        assert!(triangle.ends_with("    frame[1] = frame[2]\n    return\n}\n"), "{}", triangle);
+    }
+
+    #[test]
+    fn test_while() {
+        // Counts down from the input, testing at the top of the loop
+        let memory = [3, 100, 1006, 100, 14, 4, 100, 1001, 100, -1, 100, 1105, 1, 2, 99];
+        assert_eq!("\
+fn f_0() {
+    mem[100] = input()
+    while mem[100] != 0 {
+        output(mem[100])
+        mem[100] = mem[100] - 1
+    }
+    halt
+}
+", decompile(&memory).to_string());
+    }
+
+    #[test]
+    fn test_day_9() {
+        let input = std::fs::read_to_string("../day-09/input.txt").unwrap();
+        let memory: Vec<Word> = input.trim().split(',').map(|w| w.parse().unwrap()).collect();
+        let decompiled = decompile(&memory);
+
+        assert_eq!(vec![0, 922], decompiled.functions.iter().map(|f| f.entry).collect::<Vec<Reference>>());
+        let recursive = decompiled.function(922).unwrap().to_string();
+        assert!(recursive.contains("call f_922 (frame at frame[3])"), "{}", recursive);
+        assert!(recursive.contains("return"), "{}", recursive);
+        assert!(!decompiled.to_string().contains("invalid"));
+    }
+
+    #[test]
+    fn test_unstructured() {
+        // Jumps out of range, and through a location the program writes
+        assert_eq!("fn f_0() {\n    invalid instruction at 7\n}\n", decompile(&[1105, 1, 7]).to_string());
+        assert_eq!("\
+fn f_0() {
+    mem[5] = input()
+    goto mem[5]
+}
+", decompile(&[3, 5, 105, 1, 5, 0]).to_string());
+    }
+
+    #[test]
+    fn test_overflowing_base() {
+        let decompiled = decompile(&[109, Word::MAX, 109, 1, 204, 0, 99]).to_string();
+        assert!(decompiled.contains("output(mem[rb+0])"), "{}", decompiled);
+
+        let decompiled = decompile(&[1001, 0, Word::MIN, 0, 4, 0, 99]).to_string();
+        assert!(decompiled.contains("- 9223372036854775808"), "{}", decompiled);
+    }
+
+    #[test]
+    fn test_overflowing_constant() {
+        // Stores i64::MAX + 1 on the stack before a call
+        let decompiled = decompile(&[21101, Word::MAX, 1, 0, 1105, 1, 9, 99, 99, 99]).to_string();
+        assert!(decompiled.contains("fn f_0()"));
+    }
+}
@@ -0,0 +1,526 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::computer::{Access, Computer, CPUState, Fault, Reference, Tape, Word};
+use crate::protection::{Protection, Violation};
+
+/// How a run came to an end
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Ending {
+    Halted,
+    AwaitingInput,
+    Faulted(Fault),
+    /// Still running after the maximum number of steps
+    StepLimit,
+    /// The engine panicked, for example on a negative address
+    Panicked,
+}
+
+/// Everything compared between engines after a run
+#[derive(PartialEq, Clone, Debug)]
+pub struct Outcome {
+    pub output: Vec<Word>,
+    /// The final memory, without any trailing zeros
+    pub memory: Vec<Word>,
+    pub ending: Ending,
+}
+
+impl Outcome {
+    fn new(output: Vec<Word>, mut memory: Vec<Word>, ending: Ending) -> Outcome {
+        while memory.last() == Some(&0) {
+            memory.pop();
+        }
+        Outcome { output, memory, ending }
+    }
+}
+
+/// Bounds every engine runs within, so random programs always finish
+#[derive(Copy, Clone, Debug)]
+pub struct Limits {
+    pub max_steps: usize,
+    /// Reads and writes at or beyond this address, or below 0, fault
+    /// with a `Protection::Watch` violation instead of growing memory
+    pub memory_limit: Reference,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_steps: 1000, memory_limit: 4096 }
+    }
+}
+
+/// A way of running a tape which should behave exactly like `Computer`
+pub trait Engine {
+    fn name(&self) -> &'static str;
+    fn run(&self, tape: &Tape, input: &[Word], limits: Limits) -> Outcome;
+}
+
+/// The standard interpreter, which every other engine is checked against
+pub struct Interpreter;
+
+impl Engine for Interpreter {
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn run(&self, tape: &Tape, input: &[Word], limits: Limits) -> Outcome {
+        let mut computer = guarded_computer(tape, limits);
+        for value in input {
+            computer.io.add_input(*value);
+        }
+
+        let ending = catch_panics(|| {
+            for _ in 0 .. limits.max_steps {
+                match computer.step() {
+                    CPUState::AwaitingInstruction => {},
+                    state => return ending(state),
+                }
+            }
+            Ending::StepLimit
+        });
+        outcome(&computer, ending)
+    }
+}
+
+/// The interpreter given each input only once it stops to wait for one,
+/// which exercises resuming from `CPUState::AwaitingInput`
+pub struct Resuming;
+
+impl Engine for Resuming {
+    fn name(&self) -> &'static str {
+        "resuming"
+    }
+
+    fn run(&self, tape: &Tape, input: &[Word], limits: Limits) -> Outcome {
+        let mut computer = guarded_computer(tape, limits);
+        let mut input = input.iter();
+
+        let ending = catch_panics(|| {
+            let mut steps = 0;
+            while steps < limits.max_steps {
+                match computer.step() {
+                    CPUState::AwaitingInstruction => steps += 1,
+                    CPUState::AwaitingInput => match input.next() {
+                        Some(value) => computer.io.add_input(*value),
+                        None => return Ending::AwaitingInput,
+                    },
+                    state => return ending(state),
+                }
+            }
+            Ending::StepLimit
+        });
+        outcome(&computer, ending)
+    }
+}
+
+/// A deliberately simple implementation written from the puzzle
+/// descriptions, sharing no code with `Computer`. Where the puzzles
+/// leave something open it does what the interpreter does: unknown mode
+/// digits mean position mode, an instruction which faults has no effect,
+/// and immediate mode writes go to the parameter itself.
+pub struct Specification;
+
+impl Engine for Specification {
+    fn name(&self) -> &'static str {
+        "specification"
+    }
+
+    fn run(&self, tape: &Tape, input: &[Word], limits: Limits) -> Outcome {
+        let mut machine = Machine {
+            memory: tape.contents.clone(),
+            input: input.iter().cloned().collect(),
+            output: Vec::new(),
+            ip: 0,
+            relative_base: 0,
+            address: 0,
+            fault: None,
+            limits,
+        };
+
+        let mut ending = Ending::StepLimit;
+        for _ in 0 .. limits.max_steps {
+            match machine.step() {
+                Ok(None) => {},
+                Ok(Some(end)) => {
+                    ending = end;
+                    break;
+                },
+                Err(Panic) => {
+                    ending = Ending::Panicked;
+                    break;
+                },
+            }
+        }
+        Outcome::new(machine.output, machine.memory, ending)
+    }
+}
+
+/// Two engines disagreeing about a run
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub engine: &'static str,
+    pub expected: Outcome,
+    pub actual: Outcome,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (expected, actual) = (&self.expected, &self.actual);
+        if expected.ending != actual.ending {
+            write!(f, "{} ended with {:?}, expected {:?}", self.engine, actual.ending, expected.ending)
+        } else if expected.output != actual.output {
+            write!(f, "{} output {:?}, expected {:?}", self.engine, actual.output, expected.output)
+        } else {
+            let location = expected.memory.iter().chain(std::iter::repeat(&0))
+                .zip(actual.memory.iter().chain(std::iter::repeat(&0)))
+                .position(|(e, a)| e != a)
+                .unwrap_or(0);
+            let value = |memory: &Vec<Word>| memory.get(location).cloned().unwrap_or(0);
+            write!(f, "{} left {} at {}, expected {}", self.engine, value(&actual.memory), location, value(&expected.memory))
+        }
+    }
+}
+
+/// Runs tapes on the interpreter and a set of other engines, checking
+/// they all agree on the output, the final memory and how the run ended.
+///
+/// ```
+/// use common::differential::Harness;
+///
+/// let harness = Harness::new();
+/// let outcome = harness.check(&"109,5,21101,1,2,0,204,0,99".parse().unwrap(), &[]).unwrap();
+/// assert_eq!(vec![3], outcome.output);
+/// ```
+pub struct Harness {
+    pub limits: Limits,
+    engines: Vec<Box<dyn Engine>>,
+}
+
+impl Harness {
+    /// A harness checking the `Resuming` and `Specification` engines
+    pub fn new() -> Harness {
+        let mut harness = Harness::empty();
+        harness.add_engine(Box::new(Resuming));
+        harness.add_engine(Box::new(Specification));
+        harness
+    }
+
+    /// A harness with no engines to check against the interpreter
+    pub fn empty() -> Harness {
+        Harness { limits: Limits::default(), engines: Vec::new() }
+    }
+
+    pub fn add_engine(&mut self, engine: Box<dyn Engine>) {
+        self.engines.push(engine);
+    }
+
+    /// Runs the tape everywhere, returning the interpreter's outcome if
+    /// every engine agrees with it or the first disagreement if not
+    pub fn check(&self, tape: &Tape, input: &[Word]) -> Result<Outcome, Box<Mismatch>> {
+        let expected = Interpreter.run(tape, input, self.limits);
+        for engine in &self.engines {
+            let actual = engine.run(tape, input, self.limits);
+            if actual != expected {
+                return Err(Box::new(Mismatch { engine: engine.name(), expected, actual }));
+            }
+        }
+        Ok(expected)
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Harness::new()
+    }
+}
+
+/// A computer which faults on accesses outside the memory limit
+fn guarded_computer(tape: &Tape, limits: Limits) -> Computer {
+    let mut computer = Computer::new_with_tape(tape);
+    computer.memory.protect(Reference::MIN .. 0, Protection::Watch);
+    computer.memory.protect(limits.memory_limit .. Reference::MAX, Protection::Watch);
+    computer.memory.on_violation(Box::new(|_| true));
+    computer
+}
+
+fn ending(state: CPUState) -> Ending {
+    match state {
+        CPUState::Halted => Ending::Halted,
+        CPUState::AwaitingInput => Ending::AwaitingInput,
+        CPUState::Faulted(fault) => Ending::Faulted(fault),
+        CPUState::AwaitingInstruction => Ending::StepLimit,
+    }
+}
+
+fn catch_panics<F: FnOnce() -> Ending>(run: F) -> Ending {
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or(Ending::Panicked)
+}
+
+fn outcome(computer: &Computer, ending: Ending) -> Outcome {
+    Outcome::new(computer.io.output.clone(), computer.memory.contents().to_vec(), ending)
+}
+
+/// Where the interpreter would have panicked
+struct Panic;
+
+/// Overflow panics in debug builds and wraps in release ones
+fn overflow(checked: Option<Word>, wrapped: Word) -> Result<Word, Panic> {
+    match checked {
+        Some(value) => Ok(value),
+        None if cfg!(debug_assertions) => Err(Panic),
+        None => Ok(wrapped),
+    }
+}
+
+struct Machine {
+    memory: Vec<Word>,
+    input: VecDeque<Word>,
+    output: Vec<Word>,
+    ip: Reference,
+    relative_base: Reference,
+    /// The address of the instruction being executed
+    address: Reference,
+    /// The first violation of the memory limit during an instruction.
+    /// Later writes are skipped but the instruction carries on.
+    fault: Option<Fault>,
+    limits: Limits,
+}
+
+impl Machine {
+    fn load(&self, location: Reference) -> Result<Word, Panic> {
+        if location < 0 {
+            return Err(Panic);
+        }
+        Ok(self.memory.get(location as usize).cloned().unwrap_or(0))
+    }
+
+    fn store(&mut self, location: Reference, value: Word) {
+        if location as usize >= self.memory.len() {
+            self.memory.resize(location as usize + 1, 0);
+        }
+        self.memory[location as usize] = value;
+    }
+
+    fn guard(&mut self, access: Access, location: Reference) {
+        if (location < 0 || location >= self.limits.memory_limit) && self.fault.is_none() {
+            self.fault = Some(Fault::Protection(Violation {
+                protection: Protection::Watch,
+                access,
+                location,
+                instruction: self.address,
+            }));
+        }
+    }
+
+    fn mode(&self, parameter: u32) -> Word {
+        let word = self.memory[self.address as usize];
+        word / 10_i64.pow(parameter + 2) % 10
+    }
+
+    /// The address a parameter refers to
+    fn resolve(&self, parameter: u32) -> Result<Reference, Panic> {
+        let location = self.address + 1 + parameter as Reference;
+        let value = self.load(location)?;
+        match self.mode(parameter) {
+            1 => Ok(location),
+            2 => overflow(value.checked_add(self.relative_base), value.wrapping_add(self.relative_base)),
+            _ => Ok(value),
+        }
+    }
+
+    fn read(&mut self, parameter: u32) -> Result<Word, Panic> {
+        let location = self.resolve(parameter)?;
+        if self.mode(parameter) != 1 {
+            self.guard(Access::Read, location);
+        }
+        self.load(location)
+    }
+
+    fn write(&mut self, parameter: u32, value: Word) -> Result<(), Panic> {
+        let location = self.resolve(parameter)?;
+        self.guard(Access::Write, location);
+        if self.fault.is_none() {
+            self.store(location, value);
+        }
+        Ok(())
+    }
+
+    /// Runs one instruction, returning how the run ended if it did
+    fn step(&mut self) -> Result<Option<Ending>, Panic> {
+        self.address = self.ip;
+        let word = self.load(self.address)?;
+        let invalid = Ok(Some(Ending::Faulted(Fault::InvalidInstruction { address: self.address, instruction: word })));
+        if word < 0 {
+            return invalid;
+        }
+
+        let length = match word % 100 {
+            1 | 2 | 7 | 8 => 4,
+            3 | 4 | 9 => 2,
+            5 | 6 => 3,
+            99 => 1,
+            _ => return invalid,
+        };
+        self.ip = self.address + length;
+
+        match word % 100 {
+            1 => {
+                let (a, b) = (self.read(0)?, self.read(1)?);
+                let sum = overflow(a.checked_add(b), a.wrapping_add(b))?;
+                self.write(2, sum)?;
+            },
+            2 => {
+                let (a, b) = (self.read(0)?, self.read(1)?);
+                let product = overflow(a.checked_mul(b), a.wrapping_mul(b))?;
+                self.write(2, product)?;
+            },
+            3 => match self.input.pop_front() {
+                Some(value) => {
+                    self.write(0, value)?;
+                    if self.fault.is_some() {
+                        self.input.push_front(value);
+                    }
+                },
+                None => {
+                    self.ip = self.address;
+                    return Ok(Some(Ending::AwaitingInput));
+                },
+            },
+            4 => {
+                let value = self.read(0)?;
+                if self.fault.is_none() {
+                    self.output.push(value);
+                }
+            },
+            5 | 6 => {
+                let (condition, target) = (self.read(0)?, self.read(1)?);
+                if (condition != 0) == (word % 100 == 5) {
+                    self.ip = target;
+                }
+            },
+            7 | 8 => {
+                let (a, b) = (self.read(0)?, self.read(1)?);
+                let result = if word % 100 == 7 { a < b } else { a == b };
+                self.write(2, result as Word)?;
+            },
+            9 => {
+                let offset = self.read(0)?;
+                if self.fault.is_none() {
+                    self.relative_base = overflow(self.relative_base.checked_add(offset), self.relative_base.wrapping_add(offset))?;
+                }
+            },
+            _ => return Ok(Some(Ending::Halted)),
+        }
+
+        if let Some(fault) = self.fault {
+            self.ip = self.address;
+            return Ok(Some(Ending::Faulted(fault)));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// The size of memory random programs point into
+    const SIZE: Word = 48;
+
+    /// A random instruction using only modes valid for its parameters.
+    /// Jumps can go anywhere, so loops are bounded by the step limit.
+    fn instruction() -> impl Strategy<Value = Vec<Word>> {
+        let opcodes = prop::sample::select(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        (opcodes, prop::array::uniform3(0 .. 3_i64), prop::array::uniform3(0 .. SIZE)).prop_map(|(opcode, modes, values)| {
+            let writes: &[bool] = match opcode {
+                1 | 2 | 7 | 8 => &[false, false, true],
+                3 => &[true],
+                5 | 6 => &[false, false],
+                _ => &[false],
+            };
+
+            let mut word = opcode;
+            let mut parameters = Vec::new();
+            for (i, write) in writes.iter().enumerate() {
+                let mode = if *write && modes[i] == 1 { 0 } else { modes[i] };
+                word += mode * 10_i64.pow(i as u32 + 2);
+                parameters.push(match mode {
+                    0 => values[i],
+                    1 => values[i] - SIZE / 2,
+                    _ => values[i] % 16 - 8,
+                });
+            }
+
+            let mut words = vec![word];
+            words.extend(parameters);
+            words
+        })
+    }
+
+    fn program() -> impl Strategy<Value = Tape> {
+        (prop::collection::vec(instruction(), 1 .. 16), prop::collection::vec(-10 .. 10_i64, SIZE as usize)).prop_map(|(code, data)| {
+            let mut contents: Vec<Word> = code.into_iter().flatten().collect();
+            contents.push(99);
+            let padding = (SIZE as usize).saturating_sub(contents.len());
+            contents.extend(&data[.. padding]);
+            Tape { contents }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn engines_agree(tape in program(), input in prop::collection::vec(-10 .. 10_i64, 0 .. 4)) {
+            let mut harness = Harness::new();
+            harness.limits.max_steps = 200;
+            if let Err(mismatch) = harness.check(&tape, &input) {
+                panic!("{} running {:?} with input {:?}", mismatch, tape.contents, input);
+            }
+        }
+    }
+
+    /// An engine which gets relative mode writes wrong
+    struct Broken;
+
+    impl Engine for Broken {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn run(&self, tape: &Tape, input: &[Word], limits: Limits) -> Outcome {
+            let contents = tape.contents.iter().map(|w| if w / 10000 % 10 == 2 { w - 20000 } else { *w }).collect();
+            Specification.run(&Tape { contents }, input, limits)
+        }
+    }
+
+    #[test]
+    fn test_reports_mismatch() {
+        let mut harness = Harness::empty();
+        harness.add_engine(Box::new(Broken));
+
+        let mismatch = harness.check(&"109,5,21101,1,2,0,204,0,99".parse().unwrap(), &[]).unwrap_err();
+        assert_eq!("broken output [0], expected [3]", mismatch.to_string());
+    }
+
+    #[test]
+    fn test_memory_limit_faults() {
+        let mut harness = Harness::new();
+        harness.limits.memory_limit = 10;
+
+        let outcome = harness.check(&"1101,1,2,20,4,0,99".parse().unwrap(), &[]).unwrap();
+        assert_eq!(Ending::Faulted(Fault::Protection(Violation {
+            protection: Protection::Watch, access: Access::Write, location: 20, instruction: 0,
+        })), outcome.ending);
+        assert_eq!(7, outcome.memory.len());
+    }
+
+    #[test]
+    fn test_input_and_panics() {
+        let harness = Harness::new();
+        assert_eq!(Ending::AwaitingInput, harness.check(&"3,0,3,0,99".parse().unwrap(), &[1]).unwrap().ending);
+        assert_eq!(Ending::Panicked, harness.check(&"4,-1,99".parse().unwrap(), &[]).unwrap().ending);
+        assert_eq!(Ending::StepLimit, harness.check(&"1105,1,0".parse().unwrap(), &[]).unwrap().ending);
+    }
+}
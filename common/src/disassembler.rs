@@ -0,0 +1,169 @@
+use std::fmt;
+
+use crate::computer::{DecodedInstruction, ParameterMode, Reference, Word};
+use crate::instruction_set::InstructionSet;
+
+/// A single line of a disassembly listing, covering either one
+/// instruction and its parameters or a single data word
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub address: Reference,
+    pub words: Vec<Word>,
+    pub text: String,
+    pub is_instruction: bool,
+}
+
+impl Line {
+    /// The number of memory locations covered by this line
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// True if the passed location falls within this line
+    pub fn contains(&self, location: Reference) -> bool {
+        location >= self.address && location < self.address + self.len() as Reference
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.words.iter().map(|w| w.to_string()).collect();
+        write!(f, "{:05}  {:<24} {}", self.address, words.join(","), self.text)
+    }
+}
+
+/// Disassembles memory with a linear sweep from location 0. Anything
+/// which doesn't decode as an instruction is shown as data.
+///
+/// ```
+/// use common::disassembler::disassemble;
+///
+/// let lines = disassemble(&[1002, 4, 3, 4, 33]);
+/// assert_eq!("MUL [4], 3, [4]", lines[0].text);
+/// assert_eq!("DATA 33", lines[1].text);
+/// ```
+pub fn disassemble(memory: &[Word]) -> Vec<Line> {
+    disassemble_with_hints(memory, |_| false)
+}
+
+/// Disassembles memory with a linear sweep, using `is_instruction` to
+/// mark locations known to hold the start of an instruction (for example
+/// because they were executed). Guesses which would swallow a known
+/// instruction start are shown as data instead.
+pub fn disassemble_with_hints<F>(memory: &[Word], is_instruction: F) -> Vec<Line>
+    where F: Fn(Reference) -> bool
+{
+    disassemble_with(&InstructionSet::intcode(), memory, is_instruction)
+}
+
+/// Disassembles memory as `disassemble_with_hints` does, decoding
+/// instructions from the passed instruction set
+pub fn disassemble_with<F>(instruction_set: &InstructionSet, memory: &[Word], is_instruction: F) -> Vec<Line>
+    where F: Fn(Reference) -> bool
+{
+    let mut lines = Vec::new();
+    let mut address = 0;
+
+    while address < memory.len() {
+        let line = match decode_at(instruction_set, memory, address) {
+            Some(line) if is_instruction(address as Reference) => line,
+            Some(line) if !(1 .. line.len()).any(|i| is_instruction((address + i) as Reference)) => line,
+            _ => data_line(memory, address),
+        };
+
+        address += line.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Decodes the instruction at the passed address, if there is a valid
+/// one there with all of its parameters present
+pub(crate) fn decode_at(instruction_set: &InstructionSet, memory: &[Word], address: usize) -> Option<Line> {
+    let decoded = DecodedInstruction::from_word(memory[address], instruction_set)?;
+    let count = decoded.instruction.parameters.len();
+    if address + count >= memory.len() {
+        return None;
+    }
+
+    let parameters: Vec<String> = (0 .. count)
+        .map(|i| format_parameter(memory[address + 1 + i], decoded.modes.mode(i)))
+        .collect();
+
+    let text = if parameters.is_empty() {
+        decoded.instruction.mnemonic.to_string()
+    } else {
+        format!("{} {}", decoded.instruction.mnemonic, parameters.join(", "))
+    };
+
+    Some(Line {
+        address: address as Reference,
+        words: memory[address ..= address + count].to_vec(),
+        text,
+        is_instruction: true,
+    })
+}
+
+fn data_line(memory: &[Word], address: usize) -> Line {
+    Line {
+        address: address as Reference,
+        words: vec![memory[address]],
+        text: format!("DATA {}", memory[address]),
+        is_instruction: false,
+    }
+}
+
+fn format_parameter(value: Word, mode: ParameterMode) -> String {
+    match mode {
+        ParameterMode::Position => format!("[{}]", value),
+        ParameterMode::Immediate => value.to_string(),
+        ParameterMode::Relative if value < 0 => format!("[rb{}]", value),
+        ParameterMode::Relative => format!("[rb+{}]", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes() {
+        let lines = disassemble(&[21101, 2, -3, 7, 99]);
+        assert_eq!(2, lines.len());
+        assert_eq!("ADD 2, -3, [rb+7]", lines[0].text);
+        assert_eq!("HLT", lines[1].text);
+    }
+
+    #[test]
+    fn test_truncated_instruction_is_data() {
+        let lines = disassemble(&[1, 2, 3]);
+        assert_eq!(3, lines.len());
+        assert!(lines.iter().all(|l| !l.is_instruction));
+    }
+
+    #[test]
+    fn test_instruction_set() {
+        let lines = disassemble_with(&InstructionSet::day_2(), &[1, 0, 0, 0, 104, 1, 99], |_| false);
+        assert_eq!("ADD [0], [0], [0]", lines[0].text);
+        assert_eq!("DATA 104", lines[1].text);
+        assert_eq!("DATA 1", lines[2].text);
+        assert_eq!("HLT", lines[3].text);
+    }
+
+    #[test]
+    fn test_hints_realign() {
+        // A linear sweep would decode 2,99,0,0 as a MUL swallowing the HLT
+        let memory = [1105, 1, 4, 2, 99, 0, 0];
+        assert_eq!("MUL [99], [0], [0]", disassemble(&memory)[1].text);
+
+        let lines = disassemble_with_hints(&memory, |a| a == 0 || a == 4);
+        assert_eq!("JNZ 1, 4", lines[0].text);
+        assert_eq!("DATA 2", lines[1].text);
+        assert_eq!("HLT", lines[2].text);
+    }
+}
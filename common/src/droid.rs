@@ -0,0 +1,282 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::computer::{Computer, CPUState, Tape, Word};
+use crate::grid::{Direction, Grid, Point};
+
+/// What the droid found at a location
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Cell {
+    Wall,
+    Open,
+    /// Open, with the oxygen system in it
+    Oxygen,
+}
+
+impl Cell {
+    fn is_open(self) -> bool {
+        self != Cell::Wall
+    }
+}
+
+/// Why a maze couldn't be explored
+#[derive(PartialEq, Clone, Debug)]
+pub enum ExploreError {
+    /// The program stopped instead of waiting for the next command
+    Stopped(CPUState),
+    /// The program answered a command with something other than 0, 1 or 2
+    Status(Word),
+    /// The program answered a command with no words or more than one
+    Output(Vec<Word>),
+    /// Going back the way the droid came was blocked
+    Blocked(Point),
+}
+
+impl fmt::Display for ExploreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExploreError::Stopped(state) => write!(f, "the droid stopped with {:?}", state),
+            ExploreError::Status(status) => write!(f, "{} isn't a status", status),
+            ExploreError::Output(output) => write!(f, "expected one status but got {:?}", output),
+            ExploreError::Blocked(point) => write!(f, "couldn't go back to ({}, {})", point.x, point.y),
+        }
+    }
+}
+
+/// A repair droid controlled by a tape, as on day 15. The program is given
+/// movement commands, 1 to 4 for north, south, west and east, and answers
+/// each with what happened: 0 for hitting a wall, 1 for moving and 2 for
+/// moving onto the oxygen system.
+pub struct Droid {
+    pub computer: Computer,
+    /// Relative to where the droid started
+    pub position: Point,
+}
+
+impl Droid {
+    pub fn new(tape: &Tape) -> Droid {
+        Droid { computer: Computer::new_with_tape(tape), position: Point::default() }
+    }
+
+    fn command(direction: Direction) -> Word {
+        match direction {
+            Direction::Up => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+            Direction::Right => 4,
+        }
+    }
+
+    /// Tries to move, returning what's in that direction
+    pub fn go(&mut self, direction: Direction) -> Result<Cell, ExploreError> {
+        self.computer.run();
+        if self.computer.cpu_state() != CPUState::AwaitingInput {
+            return Err(ExploreError::Stopped(self.computer.cpu_state()));
+        }
+        self.computer.io.add_input(Droid::command(direction));
+        self.computer.run();
+
+        let output = std::mem::take(&mut self.computer.io.output);
+        let status = match output.as_slice() {
+            [status] => *status,
+            [] if self.computer.cpu_state() != CPUState::AwaitingInput => return Err(ExploreError::Stopped(self.computer.cpu_state())),
+            _ => return Err(ExploreError::Output(output)),
+        };
+        let cell = match status {
+            0 => Cell::Wall,
+            1 => Cell::Open,
+            2 => Cell::Oxygen,
+            _ => return Err(ExploreError::Status(status)),
+        };
+        if cell.is_open() {
+            self.position = self.position.step(direction);
+        }
+        Ok(cell)
+    }
+}
+
+/// Everything a droid found, with its starting point at the origin
+#[derive(Clone, Debug)]
+pub struct Map {
+    pub cells: Grid<Cell>,
+    pub oxygen: Option<Point>,
+}
+
+impl Map {
+    /// Drives a droid round the whole of a maze depth first, backing up
+    /// the way it came whenever everything around it has been seen. Only
+    /// one computer is used, paused each time it waits for a command.
+    pub fn explore(tape: &Tape) -> Result<Map, ExploreError> {
+        let mut droid = Droid::new(tape);
+        let mut map = Map { cells: Grid::new(), oxygen: None };
+        map.cells.insert(droid.position, Cell::Open);
+        let mut path: Vec<Direction> = Vec::new();
+
+        loop {
+            let position = droid.position;
+            let unseen = Direction::ALL.iter().cloned().find(|d| map.cells.get(position.step(*d)).is_none());
+            match unseen {
+                Some(direction) => {
+                    let cell = droid.go(direction)?;
+                    map.cells.insert(position.step(direction), cell);
+                    if cell == Cell::Oxygen {
+                        map.oxygen = Some(droid.position);
+                    }
+                    if cell.is_open() {
+                        path.push(direction);
+                    }
+                },
+                None => match path.pop() {
+                    Some(direction) => {
+                        let back = direction.reverse();
+                        if !droid.go(back)?.is_open() {
+                            return Err(ExploreError::Blocked(position.step(back)));
+                        }
+                    },
+                    None => return Ok(map),
+                },
+            }
+        }
+    }
+
+    fn is_open(&self, point: Point) -> bool {
+        self.cells.get(point).is_some_and(|cell| cell.is_open())
+    }
+
+    /// The number of steps to every open point reachable from a point
+    pub fn distances(&self, from: Point) -> HashMap<Point, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        if self.is_open(from) {
+            distances.insert(from, 0);
+            queue.push_back(from);
+        }
+
+        while let Some(point) = queue.pop_front() {
+            let distance = distances[&point];
+            for direction in Direction::ALL.iter() {
+                let next = point.step(*direction);
+                if self.is_open(next) && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// The moves along one of the shortest paths between two points
+    pub fn shortest_path(&self, from: Point, to: Point) -> Option<Vec<Direction>> {
+        // Walk back from the end, always to a point one step nearer
+        let distances = self.distances(from);
+        let mut path = Vec::new();
+        let mut point = to;
+        let mut distance = *distances.get(&to)?;
+        while distance > 0 {
+            let direction = Direction::ALL.iter().cloned()
+                .find(|d| distances.get(&point.step(d.reverse())) == Some(&(distance - 1)))?;
+            path.push(direction);
+            point = point.step(direction.reverse());
+            distance -= 1;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// The number of moves from the start to the oxygen system
+    pub fn oxygen_distance(&self) -> Option<usize> {
+        self.distances(Point::default()).get(&self.oxygen?).cloned()
+    }
+
+    /// How many minutes oxygen takes to fill the maze from the oxygen
+    /// system, spreading a step every minute
+    pub fn fill_time(&self) -> Option<usize> {
+        self.distances(self.oxygen?).values().max().cloned()
+    }
+
+    /// The map as text, with `D` where the droid started
+    pub fn render(&self) -> String {
+        let mut symbols = Grid::new();
+        for (point, cell) in self.cells.iter() {
+            symbols.insert(point, match cell {
+                Cell::Wall => '#',
+                Cell::Open => '.',
+                Cell::Oxygen => 'O',
+            });
+        }
+        symbols.insert(Point::default(), 'D');
+        symbols.render(|symbol| symbol.cloned().unwrap_or(' '))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    /// A droid in the maze from the day 15 example, starting at (1, 1)
+    fn example() -> Tape {
+        compile("
+            fn cell(x, y) {
+                if x == 2 && y == 3 { return 2; }
+                if (y == 1 && (x == 1 || x == 2))
+                    || (y == 2 && (x == 1 || x == 3 || x == 4))
+                    || (y == 3 && (x == 1 || x == 3)) {
+                    return 1;
+                }
+                return 0;
+            }
+            fn main() {
+                let x = 1;
+                let y = 1;
+                while 1 {
+                    let command = input();
+                    let nx = x;
+                    let ny = y;
+                    if command == 1 { ny = y - 1; }
+                    else if command == 2 { ny = y + 1; }
+                    else if command == 3 { nx = x - 1; }
+                    else { nx = x + 1; }
+                    let status = cell(nx, ny);
+                    if status != 0 { x = nx; y = ny; }
+                    output(status);
+                }
+            }").unwrap()
+    }
+
+    #[test]
+    fn test_explore() {
+        let map = Map::explore(&example()).unwrap();
+        assert_eq!(Some(Point::new(1, 2)), map.oxygen);
+        assert_eq!(" ##   \n#D.## \n#.#..#\n#.O.# \n ###  \n", map.render());
+    }
+
+    #[test]
+    fn test_paths() {
+        let map = Map::explore(&example()).unwrap();
+        assert_eq!(Some(3), map.oxygen_distance());
+        assert_eq!(Some(vec![Direction::Down, Direction::Down, Direction::Right]),
+            map.shortest_path(Point::default(), Point::new(1, 2)));
+        assert_eq!(Some(vec![]), map.shortest_path(Point::default(), Point::default()));
+        assert_eq!(None, map.shortest_path(Point::default(), Point::new(2, 0)));
+        assert_eq!(Some(4), map.fill_time());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Some(ExploreError::Stopped(CPUState::Halted)), Map::explore(&"99".parse().unwrap()).err());
+        assert_eq!(Some(ExploreError::Status(7)), Map::explore(&"3,0,104,7,99".parse().unwrap()).err());
+        assert_eq!(Some(ExploreError::Output(vec![1, 1])), Map::explore(&"3,0,104,1,104,1,99".parse().unwrap()).err());
+        assert_eq!(Some(ExploreError::Output(vec![])), Map::explore(&"3,0,3,0,99".parse().unwrap()).err());
+        assert_eq!(Some(ExploreError::Stopped(CPUState::Halted)), Map::explore(&"3,0,99".parse().unwrap()).err());
+
+        // Lets the droid move once, then never again
+        let one_way = compile("
+            fn main() {
+                input();
+                output(1);
+                while 1 { input(); output(0); }
+            }").unwrap();
+        assert_eq!(Some(ExploreError::Blocked(Point::new(0, 0))), Map::explore(&one_way).err());
+    }
+}
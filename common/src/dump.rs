@@ -0,0 +1,230 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::computer::{Computer, Reference, Word};
+
+/// A copy of a range of memory along with the registers which point
+/// into it.
+///
+/// Dumps are saved as `# name value` header lines for the start address,
+/// instruction pointer and relative base, followed by the words separated
+/// by commas like a tape.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Dump {
+    /// The address of the first word
+    pub start: Reference,
+    pub words: Vec<Word>,
+    pub instruction_pointer: Reference,
+    pub relative_base: Reference,
+}
+
+impl Dump {
+    /// Dumps all of the computer's memory
+    pub fn new(computer: &Computer) -> Dump {
+        Dump::range(computer, 0 .. computer.memory.len() as Reference)
+    }
+
+    /// Dumps part of the computer's memory
+    pub fn range(computer: &Computer, range: Range<Reference>) -> Dump {
+        Dump {
+            start: range.start,
+            words: computer.memory.export(range),
+            instruction_pointer: computer.instruction_pointer(),
+            relative_base: computer.relative_base(),
+        }
+    }
+
+    /// The addresses covered by the dump
+    pub fn addresses(&self) -> Range<Reference> {
+        self.start .. self.start + self.words.len() as Reference
+    }
+
+    /// The word at an address, or `None` if it isn't in the dump
+    pub fn get(&self, address: Reference) -> Option<Word> {
+        if !self.addresses().contains(&address) {
+            return None;
+        }
+        Some(self.words[(address - self.start) as usize])
+    }
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# start {}", self.start)?;
+        writeln!(f, "# ip {}", self.instruction_pointer)?;
+        writeln!(f, "# rb {}", self.relative_base)?;
+        let words: Vec<String> = self.words.iter().map(|w| w.to_string()).collect();
+        writeln!(f, "{}", words.join(","))
+    }
+}
+
+impl FromStr for Dump {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut dump = Dump { start: 0, words: Vec::new(), instruction_pointer: 0, relative_base: 0 };
+        for line in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            if let Some(header) = line.strip_prefix('#') {
+                let parts: Vec<&str> = header.split_whitespace().collect();
+                let value = parts.get(1).ok_or(())?.parse().map_err(|_| ())?;
+                match parts[0] {
+                    "start" => dump.start = value,
+                    "ip" => dump.instruction_pointer = value,
+                    "rb" => dump.relative_base = value,
+                    _ => return Err(()),
+                }
+            } else {
+                for word in line.split(',') {
+                    dump.words.push(word.trim().parse().map_err(|_| ())?);
+                }
+            }
+        }
+        Ok(dump)
+    }
+}
+
+/// Prints dumps in rows of `width` words, each row starting with the
+/// address of its first word. The instruction pointer is marked with
+/// `>`, the relative base with `@` (or `#` if they're the same cell),
+/// and words which differ from the snapshot are followed by `*`.
+///
+/// ```
+/// use common::computer::Computer;
+/// use common::dump::{Dump, Viewer};
+///
+/// let mut computer = Computer::new_with_tape(&"1101,2,3,5,99,0".parse().unwrap());
+/// let before = Dump::new(&computer);
+/// computer.run();
+///
+/// let viewer = Viewer { width: 4, snapshot: Some(&before) };
+/// assert_eq!("\
+/// 00000: @1101      2      3      5
+/// 00004:    99  >   5*
+/// ", viewer.view(&Dump::new(&computer)));
+/// ```
+pub struct Viewer<'a> {
+    pub width: usize,
+    /// Words which have changed since this dump are marked
+    pub snapshot: Option<&'a Dump>,
+}
+
+impl<'a> Default for Viewer<'a> {
+    fn default() -> Self {
+        Viewer { width: 8, snapshot: None }
+    }
+}
+
+impl<'a> Viewer<'a> {
+    pub fn view(&self, dump: &Dump) -> String {
+        let column = dump.words.iter().map(|w| w.to_string().len()).max().unwrap_or(1);
+        let mut result = String::new();
+
+        for (row, words) in dump.words.chunks(self.width.max(1)).enumerate() {
+            let row_start = dump.start + (row * self.width.max(1)) as Reference;
+            result += &format!("{:05}:", row_start);
+
+            for (i, word) in words.iter().enumerate() {
+                let address = row_start + i as Reference;
+                let marker = match (address == dump.instruction_pointer, address == dump.relative_base) {
+                    (true, true) => '#',
+                    (true, false) => '>',
+                    (false, true) => '@',
+                    (false, false) => ' ',
+                };
+                let changed = match self.snapshot {
+                    Some(snapshot) if snapshot.get(address).unwrap_or(0) != *word => '*',
+                    _ => ' ',
+                };
+                result += &format!(" {}{:>width$}{}", marker, word, changed, width = column);
+            }
+            result = result.trim_end_matches(' ').to_string();
+            result.push('\n');
+        }
+        result
+    }
+}
+
+/// A word which differs between two dumps
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Difference {
+    pub address: Reference,
+    pub before: Word,
+    pub after: Word,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:05}: {} -> {}", self.address, self.before, self.after)
+    }
+}
+
+/// Every word which differs between two dumps, in address order. Words
+/// missing from one of the dumps count as 0.
+pub fn diff(before: &Dump, after: &Dump) -> Vec<Difference> {
+    let start = before.start.min(after.start);
+    let end = before.addresses().end.max(after.addresses().end);
+    (start .. end)
+        .map(|address| Difference {
+            address,
+            before: before.get(address).unwrap_or(0),
+            after: after.get(address).unwrap_or(0),
+        })
+        .filter(|d| d.before != d.after)
+        .collect()
+}
+
+/// Describes the differences between two dumps, one per line, including
+/// changes to the instruction pointer and relative base
+pub fn describe_diff(before: &Dump, after: &Dump) -> String {
+    let mut result = String::new();
+    if before.instruction_pointer != after.instruction_pointer {
+        result += &format!("ip: {} -> {}\n", before.instruction_pointer, after.instruction_pointer);
+    }
+    if before.relative_base != after.relative_base {
+        result += &format!("rb: {} -> {}\n", before.relative_base, after.relative_base);
+    }
+    for difference in diff(before, after) {
+        result += &format!("{}\n", difference);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut computer = Computer::new_with_tape(&"109,19,204,-15,99".parse().unwrap());
+        computer.run();
+        let dump = Dump::range(&computer, 2 .. 7);
+
+        assert_eq!(vec![204, -15, 99, 0, 0], dump.words);
+        assert_eq!("# start 2\n# ip 5\n# rb 19\n204,-15,99,0,0\n", dump.to_string());
+        assert_eq!(Ok(dump.clone()), dump.to_string().parse());
+        assert_eq!(Err(()), "# pc 3\n1,2".parse::<Dump>());
+    }
+
+    #[test]
+    fn test_view() {
+        let mut computer = Computer::new_with_tape(&"109,3,99".parse().unwrap());
+        computer.run();
+
+        let viewer = Viewer { width: 2, snapshot: None };
+        assert_eq!("\
+00000:  109     3
+00002:   99  #  0
+00004:    0
+", viewer.view(&Dump::range(&computer, 0 .. 5)));
+    }
+
+    #[test]
+    fn test_diff() {
+        let before: Dump = "# start 0\n# ip 0\n# rb 0\n1,2,3".parse().unwrap();
+        let after: Dump = "# start 1\n# ip 4\n# rb 0\n2,4,3,5".parse().unwrap();
+
+        assert_eq!(Difference { address: 2, before: 3, after: 4 }, diff(&before, &after)[1]);
+        assert_eq!("ip: 0 -> 4\n00000: 1 -> 0\n00002: 3 -> 4\n00003: 0 -> 3\n00004: 0 -> 5\n", describe_diff(&before, &after));
+    }
+}
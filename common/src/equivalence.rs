@@ -0,0 +1,230 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::computer::{Tape, Word};
+use crate::differential::{Engine, Ending, Interpreter, Limits, Outcome};
+
+/// The inputs two tapes are compared on
+#[derive(PartialEq, Clone, Debug)]
+pub enum Domain {
+    /// Every input with one value from each range in turn
+    Ranges(Vec<Range<Word>>),
+    /// Exactly these inputs
+    Inputs(Vec<Vec<Word>>),
+}
+
+impl Domain {
+    /// Every single word input in the range
+    pub fn values(range: Range<Word>) -> Domain {
+        Domain::Ranges(vec![range])
+    }
+
+    /// The number of inputs. Panics if there are too many to count.
+    pub fn len(&self) -> usize {
+        self.checked_len().expect("too many inputs to count")
+    }
+
+    /// The number of inputs, or `None` if there are more than fit in a
+    /// `usize`
+    pub fn checked_len(&self) -> Option<usize> {
+        match self {
+            Domain::Ranges(ranges) if ranges.iter().any(|range| range.is_empty()) => Some(0),
+            Domain::Ranges(ranges) => ranges.iter().try_fold(1usize, |total, range| total.checked_mul(width(range)?)),
+            Domain::Inputs(inputs) => Some(inputs.len()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checked_len() == Some(0)
+    }
+
+    /// The input at an index from 0 to `len()`. The first range's value
+    /// changes slowest and the last's fastest.
+    pub fn input(&self, index: usize) -> Vec<Word> {
+        match self {
+            Domain::Ranges(ranges) => {
+                let mut index = index;
+                let mut input = Vec::with_capacity(ranges.len());
+                for range in ranges.iter().rev() {
+                    let n = width(range).expect("too many values to count");
+                    input.push((range.start as i128 + (index % n) as i128) as Word);
+                    index /= n;
+                }
+                input.reverse();
+                input
+            },
+            Domain::Inputs(inputs) => inputs[index].clone(),
+        }
+    }
+}
+
+fn width(range: &Range<Word>) -> Option<usize> {
+    usize::try_from((range.end as i128 - range.start as i128).max(0)).ok()
+}
+
+/// An input the two tapes handled differently
+#[derive(PartialEq, Clone, Debug)]
+pub struct Counterexample {
+    pub input: Vec<Word>,
+    pub left: Outcome,
+    pub right: Outcome,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |outcome: &Outcome| format!("output {:?} and {}", outcome.output, match outcome.ending {
+            Ending::Halted => "halted".to_string(),
+            Ending::AwaitingInput => "waited for input".to_string(),
+            Ending::Faulted(fault) => format!("faulted with {:?}", fault),
+            Ending::StepLimit => "was still running".to_string(),
+            Ending::Panicked => "panicked".to_string(),
+        });
+        write!(f, "with input {:?} the left tape gave {}, the right tape gave {}",
+            self.input, describe(&self.left), describe(&self.right))
+    }
+}
+
+/// The result of comparing two tapes over a domain
+#[derive(PartialEq, Clone, Debug)]
+pub struct Report {
+    /// The number of inputs tried
+    pub checked: usize,
+    /// The number of those the tapes disagreed on
+    pub differences: usize,
+    /// The smallest input the tapes disagreed on
+    pub counterexample: Option<Counterexample>,
+}
+
+impl Report {
+    pub fn equivalent(&self) -> bool {
+        self.counterexample.is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.counterexample {
+            None => write!(f, "equivalent on all {} inputs", self.checked),
+            Some(counterexample) => write!(f, "differ on {} of {} inputs, for example {}",
+                self.differences, self.checked, counterexample),
+        }
+    }
+}
+
+/// Inputs are smallest when shortest, then by the sum of their sizes,
+/// then in order
+fn size(input: &[Word]) -> (usize, u128, Vec<Word>) {
+    (input.len(), input.iter().map(|v| v.unsigned_abs() as u128).sum(), input.to_vec())
+}
+
+/// The smaller of two counterexamples by the size of their inputs
+fn smallest(a: Option<Counterexample>, b: Option<Counterexample>) -> Option<Counterexample> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if size(&b.input) < size(&a.input) { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Runs both tapes on every input in the domain, in parallel, and checks
+/// they produce the same output and end the same way. Memory isn't
+/// compared, so a tape can be checked against an optimised or patched
+/// version of itself. Panics if the domain has too many inputs to count.
+///
+/// ```
+/// use common::differential::Limits;
+/// use common::equivalence::{check, Domain};
+///
+/// // Outputs double the input, once by adding and once by multiplying
+/// let add = "3,9,1,9,9,9,4,9,99,0".parse().unwrap();
+/// let mul = "3,9,1002,9,2,9,4,9,99,0".parse().unwrap();
+/// assert!(check(&add, &mul, &Domain::values(-100 .. 100), Limits::default()).equivalent());
+///
+/// // Squares the input instead
+/// let square = "3,9,2,9,9,9,4,9,99,0".parse().unwrap();
+/// let report = check(&add, &square, &Domain::values(-100 .. 100), Limits::default());
+/// assert_eq!(vec![-1], report.counterexample.unwrap().input);
+/// ```
+pub fn check(left: &Tape, right: &Tape, domain: &Domain, limits: Limits) -> Report {
+    let checked = domain.len();
+    let (differences, counterexample) = (0 .. checked).into_par_iter()
+        .filter_map(|index| {
+            let input = domain.input(index);
+            let left = Interpreter.run(left, &input, limits);
+            let right = Interpreter.run(right, &input, limits);
+            if left.output == right.output && left.ending == right.ending {
+                None
+            } else {
+                Some((1, Some(Counterexample { input, left, right })))
+            }
+        })
+        .reduce(|| (0, None), |(n, a), (m, b)| (n + m, smallest(a, b)));
+
+    Report { checked, differences, counterexample }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Fault;
+
+    #[test]
+    fn test_domain() {
+        let inputs = |domain: Domain| (0 .. domain.len()).map(|i| domain.input(i)).collect::<Vec<_>>();
+        assert_eq!(vec![vec![0, 5], vec![0, 6], vec![1, 5], vec![1, 6]], inputs(Domain::Ranges(vec![0 .. 2, 5 .. 7])));
+        assert_eq!(vec![Vec::<Word>::new()], inputs(Domain::Ranges(vec![])));
+        assert!(Domain::Ranges(vec![0 .. 2, 3 .. 3]).is_empty());
+        assert_eq!(vec![vec![3], vec![1, 2]], inputs(Domain::Inputs(vec![vec![3], vec![1, 2]])));
+
+        let domain = Domain::values(Word::MIN .. Word::MAX);
+        assert_eq!(Some(u64::MAX as usize), domain.checked_len());
+        assert_eq!(vec![Word::MAX - 1], domain.input(domain.len() - 1));
+        assert_eq!(None, Domain::Ranges(vec![Word::MIN .. Word::MAX, 0 .. 2]).checked_len());
+    }
+
+    #[test]
+    fn test_minimal_counterexample() {
+        // Compares the input to 8, and a version patched to compare to 3
+        let original = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let patched = "3,9,8,9,10,9,4,9,99,-1,3".parse().unwrap();
+
+        let report = check(&original, &patched, &Domain::values(-10 .. 10), Limits::default());
+        assert_eq!(20, report.checked);
+        assert_eq!(2, report.differences);
+        let counterexample = report.counterexample.unwrap();
+        assert_eq!(vec![3], counterexample.input);
+        assert_eq!(vec![0], counterexample.left.output);
+        assert_eq!(vec![1], counterexample.right.output);
+
+        let inputs = Domain::Inputs(vec![vec![1, 2, 3], vec![8, 9], vec![5]]);
+        let report = check(&original, &patched, &inputs, Limits::default());
+        assert_eq!(vec![8, 9], report.counterexample.unwrap().input);
+    }
+
+    #[test]
+    fn test_endings() {
+        let halts = "3,5,4,5,99,0".parse().unwrap();
+        let waits = "3,7,4,7,3,7,99,0".parse().unwrap();
+        let faults = "3,5,4,5,98,0".parse().unwrap();
+        let domain = Domain::Inputs(vec![vec![4]]);
+
+        let report = check(&halts, &waits, &domain, Limits::default());
+        assert_eq!(Ending::AwaitingInput, report.counterexample.unwrap().right.ending);
+
+        let report = check(&halts, &faults, &domain, Limits::default());
+        let counterexample = report.counterexample.unwrap();
+        assert_eq!(Ending::Faulted(Fault::InvalidInstruction { address: 4, instruction: 98 }), counterexample.right.ending);
+        assert_eq!("with input [4] the left tape gave output [4] and halted, \
+            the right tape gave output [4] and faulted with InvalidInstruction { address: 4, instruction: 98 }", counterexample.to_string());
+    }
+
+    #[test]
+    fn test_report() {
+        let tape: Tape = "3,5,4,5,99,0".parse().unwrap();
+        let report = check(&tape, &tape, &Domain::values(0 .. 3), Limits::default());
+        assert!(report.equivalent());
+        assert_eq!("equivalent on all 3 inputs", report.to_string());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::ops::Add;
+
+use crate::computer::Word;
+
+/// A position on a grid, with `y` increasing downwards as on a screen
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Debug, Default)]
+pub struct Point {
+    pub x: Word,
+    pub y: Word,
+}
+
+impl Point {
+    pub fn new(x: Word, y: Word) -> Point {
+        Point { x, y }
+    }
+
+    /// The point one step away in a direction
+    pub fn step(self, direction: Direction) -> Point {
+        self + direction.offset()
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+    pub fn turn_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Right => Direction::Up,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+        }
+    }
+
+    pub fn turn_right(self) -> Direction {
+        self.turn_left().reverse()
+    }
+
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Right => Direction::Left,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+        }
+    }
+
+    /// The change in position from a step this way
+    pub fn offset(self) -> Point {
+        match self {
+            Direction::Up => Point::new(0, -1),
+            Direction::Right => Point::new(1, 0),
+            Direction::Down => Point::new(0, 1),
+            Direction::Left => Point::new(-1, 0),
+        }
+    }
+}
+
+/// Values at points on an unbounded grid, with nothing anywhere else
+#[derive(PartialEq, Clone, Debug)]
+pub struct Grid<T> {
+    cells: HashMap<Point, T>,
+}
+
+impl<T> Grid<T> {
+    pub fn new() -> Grid<T> {
+        Grid { cells: HashMap::new() }
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.cells.get(&point)
+    }
+
+    /// Puts a value at a point, returning what was there before
+    pub fn insert(&mut self, point: Point, value: T) -> Option<T> {
+        self.cells.insert(point, value)
+    }
+
+    /// The number of points with a value
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.cells.iter().map(|(point, value)| (*point, value))
+    }
+
+    /// The top left and bottom right corners of the smallest rectangle
+    /// holding every value
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let mut points = self.cells.keys();
+        let first = *points.next()?;
+        Some(points.fold((first, first), |(min, max), p| {
+            (Point::new(min.x.min(p.x), min.y.min(p.y)), Point::new(max.x.max(p.x), max.y.max(p.y)))
+        }))
+    }
+
+    /// Draws the bounds of the grid a character per point, with a newline
+    /// after each row
+    pub fn render<F: Fn(Option<&T>) -> char>(&self, draw: F) -> String {
+        let mut text = String::new();
+        if let Some((min, max)) = self.bounds() {
+            for y in min.y ..= max.y {
+                text.extend((min.x ..= max.x).map(|x| draw(self.get(Point::new(x, y)))));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Draws the bounds of the grid as a binary PPM image, with each point
+    /// a `scale` pixel square of an RGB colour
+    pub fn image<F: Fn(Option<&T>) -> [u8; 3]>(&self, scale: usize, colour: F) -> Vec<u8> {
+        let (min, max) = self.bounds().unwrap_or_default();
+        let (width, height) = if self.is_empty() {
+            (0, 0)
+        } else {
+            ((max.x - min.x + 1) as usize, (max.y - min.y + 1) as usize)
+        };
+
+        let mut image = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+        for y in 0 .. height {
+            let row: Vec<u8> = (0 .. width)
+                .flat_map(|x| {
+                    let rgb = colour(self.get(Point::new(min.x + x as Word, min.y + y as Word)));
+                    rgb.repeat(scale)
+                })
+                .collect();
+            for _ in 0 .. scale {
+                image.extend_from_slice(&row);
+            }
+        }
+        image
+    }
+}
+
+impl<T> Default for Grid<T> {
+    fn default() -> Self {
+        Grid::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directions() {
+        for direction in Direction::ALL.iter() {
+            assert_eq!(*direction, direction.turn_left().turn_right());
+            assert_eq!(direction.reverse(), direction.turn_right().turn_right());
+            assert_eq!(Point::default(), Point::default().step(*direction).step(direction.reverse()));
+        }
+        assert_eq!(Direction::Right, Direction::Up.turn_right());
+        assert_eq!(Point::new(3, 1), Point::new(3, 2).step(Direction::Up));
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut grid = Grid::new();
+        assert_eq!(None, grid.bounds());
+        assert_eq!("", grid.render(|_| '#'));
+
+        grid.insert(Point::new(-1, 2), 'a');
+        grid.insert(Point::new(1, 3), 'b');
+        assert_eq!(None, grid.insert(Point::new(0, 2), 'c'));
+        assert_eq!(Some('c'), grid.insert(Point::new(0, 2), 'd'));
+        assert_eq!(3, grid.len());
+        assert_eq!(Some((Point::new(-1, 2), Point::new(1, 3))), grid.bounds());
+        assert_eq!("ad.\n..b\n", grid.render(|c| c.cloned().unwrap_or('.')));
+    }
+
+    #[test]
+    fn test_image() {
+        let mut grid = Grid::new();
+        grid.insert(Point::new(5, 5), true);
+        grid.insert(Point::new(6, 5), false);
+
+        let image = grid.image(2, |cell| if cell == Some(&true) { [255, 255, 255] } else { [0, 0, 0] });
+        let header = b"P6\n4 2\n255\n";
+        assert_eq!(header, &image[.. header.len()]);
+        let white = [255; 6];
+        let black = [0; 6];
+        let row = [&white[..], &black[..]].concat();
+        assert_eq!([&row[..], &row[..]].concat(), &image[header.len() ..]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::{Ref, RefCell};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::computer::{CPUState, Tape, Word};
+use crate::grid::Point;
+use crate::peripheral::{Bus, Robot};
+
+pub const BLACK: Word = 0;
+pub const WHITE: Word = 1;
+
+/// A hull painting robot controlled by a tape, as on day 11. The program
+/// reads the colour of the panel under the robot and outputs the colour
+/// to paint it and which way to turn. Rather than rerunning the program for
+/// each panel, the computer pauses whenever it wants to know a colour.
+///
+/// ```
+/// use common::compiler::compile;
+/// use common::hull::{Hull, WHITE};
+///
+/// // Paints a line of three panels, turning right and left in turn
+/// let tape = compile("
+///     fn main() {
+///         let i = 0;
+///         while i < 3 {
+///             input();
+///             output(1);
+///             output(i == 0 || i == 2);
+///             i = i + 1;
+///         }
+///     }").unwrap();
+/// let mut hull = Hull::new(&tape, WHITE);
+/// hull.run();
+///
+/// assert_eq!(3, hull.painted());
+/// assert_eq!(" #\n##\n", hull.render());
+/// ```
+pub struct Hull {
+    pub bus: Bus,
+    robot: Rc<RefCell<Robot>>,
+}
+
+impl Hull {
+    /// A robot at the origin of a black hull, standing on a panel of the
+    /// colour given
+    pub fn new(tape: &Tape, start: Word) -> Hull {
+        let mut robot = Robot::new();
+        robot.panels.insert(Point::default(), start);
+        let robot = Rc::new(RefCell::new(robot));
+
+        let mut bus = Bus::new(tape);
+        bus.attach(Rc::clone(&robot));
+        Hull { bus, robot }
+    }
+
+    /// Runs the program until it stops, which is when it halts unless
+    /// something went wrong
+    pub fn run(&mut self) -> CPUState {
+        self.bus.run()
+    }
+
+    pub fn robot(&self) -> Ref<'_, Robot> {
+        self.robot.borrow()
+    }
+
+    /// The number of panels painted at least once
+    pub fn painted(&self) -> usize {
+        self.robot().painted.len()
+    }
+
+    /// The hull as text, with `#` for white panels. The picture covers
+    /// every panel painted, or given a colour to start with.
+    pub fn render(&self) -> String {
+        self.robot().panels.render(|colour| if colour == Some(&WHITE) { '#' } else { ' ' })
+    }
+
+    /// The hull as a PPM image, with each panel a square `scale` pixels
+    /// across
+    pub fn image(&self, scale: usize) -> Vec<u8> {
+        self.robot().panels.image(scale, |colour| if colour == Some(&WHITE) { [255; 3] } else { [0; 3] })
+    }
+
+    pub fn save_image<P: AsRef<Path>>(&self, path: P, scale: usize) -> io::Result<()> {
+        fs::write(path, self.image(scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    #[test]
+    fn test_example() {
+        // The moves from the day 11 example, ignoring the colours read
+        let tape = compile("
+            fn step(colour, turn) {
+                input();
+                output(colour);
+                output(turn);
+            }
+            fn main() {
+                step(1, 0); step(0, 0); step(1, 0); step(1, 0);
+                step(0, 1); step(1, 0); step(1, 0);
+            }").unwrap();
+        let mut hull = Hull::new(&tape, BLACK);
+        assert_eq!(CPUState::Halted, hull.run());
+
+        assert_eq!(6, hull.painted());
+        assert_eq!("  #\n  #\n## \n", hull.render());
+    }
+
+    #[test]
+    fn test_reads_colours() {
+        // Goes round a square, swapping the colour of each panel
+        let laps = |n: usize| {
+            let tape = compile(&format!("
+                fn main() {{
+                    let i = 0;
+                    while i < {} {{
+                        output(!input());
+                        output(1);
+                        i = i + 1;
+                    }}
+                }}", n * 4)).unwrap();
+            let mut hull = Hull::new(&tape, WHITE);
+            assert_eq!(CPUState::Halted, hull.run());
+            (hull.painted(), hull.render())
+        };
+
+        assert_eq!((4, " #\n##\n".to_string()), laps(1));
+        assert_eq!((4, "# \n  \n".to_string()), laps(2));
+    }
+
+    #[test]
+    fn test_image() {
+        let mut hull = Hull::new(&"99".parse().unwrap(), WHITE);
+        hull.run();
+        assert_eq!(0, hull.painted());
+        assert_eq!("#\n", hull.render());
+        assert_eq!(b"P6\n3 3\n255\n".iter().chain([255; 27].iter()).cloned().collect::<Vec<u8>>(), hull.image(3));
+    }
+}
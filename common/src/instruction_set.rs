@@ -0,0 +1,329 @@
+use std::fmt;
+
+use crate::computer::{CPUState, Execution, OpCode, Reference, Word};
+
+/// How an instruction uses one of its parameters
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Role {
+    /// The parameter is read as a value
+    Read,
+    /// The parameter is the location a result is written to
+    Write,
+}
+
+/// Carries out an instruction, returning the state the CPU is in after it
+pub type Handler = fn(&mut Execution) -> CPUState;
+
+/// The definition of a single instruction
+#[derive(Copy, Clone)]
+pub struct Instruction {
+    /// The opcode, from 1 to 99
+    pub opcode: Word,
+    pub mnemonic: &'static str,
+    /// The role of each parameter following the opcode
+    pub parameters: &'static [Role],
+    pub handler: Handler,
+}
+
+impl fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.mnemonic, self.opcode)
+    }
+}
+
+/// The instructions a computer understands, looked up by opcode.
+///
+/// Dialects can be built up from one of the standard sets by registering
+/// extra instructions:
+///
+/// ```
+/// use common::computer::{Computer, CPUState};
+/// use common::instruction_set::{Instruction, InstructionSet, Role};
+///
+/// let mut instruction_set = InstructionSet::intcode();
+/// instruction_set.register(Instruction {
+///     opcode: 10,
+///     mnemonic: "MOD",
+///     parameters: &[Role::Read, Role::Read, Role::Write],
+///     handler: |e| {
+///         let result = e.read(0) % e.read(1);
+///         e.write(2, result);
+///         CPUState::AwaitingInstruction
+///     },
+/// });
+///
+/// let mut computer = Computer::new();
+/// computer.set_instruction_set(instruction_set);
+/// computer.reset_and_load_tape(&"1110,17,5,0,99".parse().unwrap());
+/// assert_eq!(2, computer.run());
+/// ```
+#[derive(Clone, Debug)]
+pub struct InstructionSet {
+    instructions: Vec<Option<Instruction>>,
+}
+
+impl InstructionSet {
+    /// An instruction set with no instructions in it
+    pub fn empty() -> InstructionSet {
+        InstructionSet { instructions: vec![None; 100] }
+    }
+
+    /// The instructions from day 2: add, multiply and halt
+    pub fn day_2() -> InstructionSet {
+        let mut set = InstructionSet::empty();
+        set.register(Instruction { opcode: OpCode::Add as Word, mnemonic: "ADD", parameters: &[Role::Read, Role::Read, Role::Write], handler: op_add });
+        set.register(Instruction { opcode: OpCode::Mul as Word, mnemonic: "MUL", parameters: &[Role::Read, Role::Read, Role::Write], handler: op_mul });
+        set.register(Instruction { opcode: OpCode::Halt as Word, mnemonic: "HLT", parameters: &[], handler: op_halt });
+        set
+    }
+
+    /// The instructions up to day 5, adding IO, jumps and comparisons
+    pub fn day_5() -> InstructionSet {
+        let mut set = InstructionSet::day_2();
+        set.register(Instruction { opcode: OpCode::ConsumeInput as Word, mnemonic: "IN", parameters: &[Role::Write], handler: op_consume_input });
+        set.register(Instruction { opcode: OpCode::ProduceOutput as Word, mnemonic: "OUT", parameters: &[Role::Read], handler: op_produce_output });
+        set.register(Instruction { opcode: OpCode::JumpIfNotZero as Word, mnemonic: "JNZ", parameters: &[Role::Read, Role::Read], handler: op_jump_not_zero });
+        set.register(Instruction { opcode: OpCode::JumpIfZero as Word, mnemonic: "JZ", parameters: &[Role::Read, Role::Read], handler: op_jump_zero });
+        set.register(Instruction { opcode: OpCode::LessThan as Word, mnemonic: "LT", parameters: &[Role::Read, Role::Read, Role::Write], handler: op_less_than });
+        set.register(Instruction { opcode: OpCode::Equal as Word, mnemonic: "EQ", parameters: &[Role::Read, Role::Read, Role::Write], handler: op_equal });
+        set
+    }
+
+    /// The complete Intcode instruction set, as of day 9
+    pub fn intcode() -> InstructionSet {
+        let mut set = InstructionSet::day_5();
+        set.register(Instruction { opcode: OpCode::AdjustRelativeBase as Word, mnemonic: "ARB", parameters: &[Role::Read], handler: op_adjust_relative_base });
+        set
+    }
+
+    /// Adds an instruction, replacing any existing one with the same opcode
+    pub fn register(&mut self, instruction: Instruction) {
+        assert!(instruction.opcode > 0 && instruction.opcode < 100, "Opcodes must be between 1 and 99");
+        self.instructions[instruction.opcode as usize] = Some(instruction);
+    }
+
+    /// Removes the instruction with the passed opcode, if there is one
+    pub fn remove(&mut self, opcode: Word) {
+        if let Some(slot) = self.instructions.get_mut(opcode as usize) {
+            *slot = None;
+        }
+    }
+
+    /// Looks up the instruction for an opcode
+    pub fn get(&self, opcode: Word) -> Option<&Instruction> {
+        if opcode < 0 {
+            return None;
+        }
+        self.instructions.get(opcode as usize)?.as_ref()
+    }
+
+    /// All the registered instructions, in opcode order
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.iter().flatten()
+    }
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        InstructionSet::intcode()
+    }
+}
+
+fn op_add(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+    let result = a + b;
+
+    if e.debug() {
+        println!("CPU: read {}, read {}, writing {}", a, b, result);
+    }
+
+    e.write(2, result);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_mul(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+    let result = a * b;
+
+    if e.debug() {
+        println!("CPU: read {}, read {}, writing {}", a, b, result);
+    }
+
+    e.write(2, result);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_consume_input(e: &mut Execution) -> CPUState {
+    let value = match e.input() {
+        Some(value) => value,
+        None => {
+            if e.debug() {
+                println!("CPU: no value to consume, waiting");
+            }
+            return CPUState::AwaitingInput;
+        },
+    };
+
+    if e.debug() {
+        println!("CPU: consumed {}", value);
+    }
+
+    e.write(0, value);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_produce_output(e: &mut Execution) -> CPUState {
+    let value = e.read(0);
+
+    if e.debug() {
+        println!("CPU: producing {}", value);
+    }
+
+    e.output(value);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_jump_not_zero(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+
+    if a != 0 {
+        if e.debug() {
+            println!("CPU: adjusting IP to {} as {} != 0", b, a);
+        }
+
+        e.jump(b as Reference);
+    } else if e.debug() {
+        println!("CPU: not adjusting IP as {} == 0", a);
+    }
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_jump_zero(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+
+    if a == 0 {
+        if e.debug() {
+            println!("CPU: adjusting IP to {} as {} == 0", b, a);
+        }
+
+        e.jump(b as Reference);
+    } else if e.debug() {
+        println!("CPU: not adjusting IP as {} != 0", a);
+    }
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_less_than(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+    let output = if a < b { 1 } else { 0 };
+
+    if e.debug() {
+        println!("CPU: read {}, read {}, writing {}", a, b, output);
+    }
+
+    e.write(2, output);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_equal(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    let b = e.read(1);
+    let output = if a == b { 1 } else { 0 };
+
+    if e.debug() {
+        println!("CPU: read {}, read {}, writing {}", a, b, output);
+    }
+
+    e.write(2, output);
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_adjust_relative_base(e: &mut Execution) -> CPUState {
+    let a = e.read(0);
+    e.adjust_relative_base(a);
+
+    if e.debug() {
+        println!("CPU: adjusted relative base by {} to {}", a, e.relative_base());
+    }
+
+    CPUState::AwaitingInstruction
+}
+
+fn op_halt(e: &mut Execution) -> CPUState {
+    if e.debug() {
+        println!("CPU: halting");
+    }
+    CPUState::Halted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer::{Computer, Fault};
+    use super::*;
+
+    #[test]
+    fn test_day_2_rejects_later_instructions() {
+        let mut computer = Computer::new();
+        computer.set_instruction_set(InstructionSet::day_2());
+        computer.reset_and_load_tape(&"1,0,0,0,104,1,99".parse().unwrap());
+        computer.run();
+
+        assert_eq!(CPUState::Faulted(Fault::InvalidInstruction { address: 4, instruction: 104 }), computer.cpu_state());
+        assert_eq!(2, computer.memory.read_direct(0));
+    }
+
+    #[test]
+    fn test_day_2_runs_day_2_tape() {
+        let mut computer = Computer::new();
+        computer.set_instruction_set(InstructionSet::day_2());
+        computer.reset_and_load_tape(&"1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap());
+        assert_eq!(3500, computer.run());
+        assert_eq!(CPUState::Halted, computer.cpu_state());
+    }
+
+    #[test]
+    fn test_replace_instruction() {
+        let mut instruction_set = InstructionSet::intcode();
+        instruction_set.register(Instruction {
+            opcode: OpCode::ProduceOutput as Word,
+            mnemonic: "OUT2",
+            parameters: &[Role::Read],
+            handler: |e| {
+                let value = e.read(0);
+                e.output(value * 2);
+                CPUState::AwaitingInstruction
+            },
+        });
+
+        let mut computer = Computer::new();
+        computer.set_instruction_set(instruction_set);
+        computer.reset_and_load_tape(&"104,21,99".parse().unwrap());
+        computer.run();
+        assert_eq!(vec![42], computer.io.output);
+    }
+
+    #[test]
+    fn test_remove_and_get() {
+        let mut instruction_set = InstructionSet::intcode();
+        assert_eq!(10, instruction_set.instructions().count());
+        assert_eq!("ARB", instruction_set.get(9).unwrap().mnemonic);
+
+        instruction_set.remove(9);
+        assert!(instruction_set.get(9).is_none());
+        assert!(instruction_set.get(-1).is_none());
+        assert!(instruction_set.get(100).is_none());
+    }
+}
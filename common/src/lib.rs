@@ -1,4 +1,30 @@
+pub mod arcade;
+pub mod async_computer;
+pub mod binary;
+pub mod compiler;
 pub mod computer;
+pub mod console;
+pub mod coverage;
+pub mod decompiler;
+pub mod differential;
+pub mod disassembler;
+pub mod droid;
+pub mod dump;
+pub mod equivalence;
+pub mod grid;
+pub mod hull;
+pub mod instruction_set;
+pub mod network;
+pub mod optimizer;
+pub mod peripheral;
+pub mod protection;
+pub mod recording;
+pub mod search;
+pub mod self_modification;
+pub mod suite;
+pub mod symbolic;
+pub mod tape_file;
+pub mod visualizer;
 
 use std::fs;
 
@@ -25,7 +51,7 @@ pub trait Puzzle {
         let input = self.input();
         for line in input.lines() {
             let trimmed = line.trim();
-            if trimmed.len() == 0 {
+            if trimmed.is_empty() {
                 continue;
             }
             let item = self.parse_line(line);
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+
+use crate::computer::{Computer, CPUState, Reference, Tape, Word};
+
+/// The address watched by the NAT
+pub const NAT_ADDRESS: Reference = 255;
+
+/// An `(x, y)` pair sent between computers
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Packet {
+    pub source: Reference,
+    pub destination: Reference,
+    pub x: Word,
+    pub y: Word,
+}
+
+/// Something which happened to a node, in the order it happened
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Event {
+    Sent(Packet),
+    Received(Packet),
+    /// The node asked for input with nothing queued and was given -1
+    Starved,
+}
+
+/// One computer on the network along with its incoming packets
+pub struct Node {
+    pub address: Reference,
+    pub computer: Computer,
+    pub queue: VecDeque<Packet>,
+    /// Everything sent, received and asked for by the node
+    pub log: Vec<Event>,
+    /// Input requests answered with -1 since the node last sent or
+    /// received a packet
+    idle_reads: usize,
+}
+
+impl Node {
+    fn new(address: Reference, tape: &Tape) -> Node {
+        let mut computer = Computer::new_with_tape(tape);
+        computer.io.add_input(address);
+        Node { address, computer, queue: VecDeque::new(), log: Vec::new(), idle_reads: 0 }
+    }
+
+    /// Runs the node until it asks for input with nothing queued a second
+    /// time, stops, or uses up its slice of instructions, returning what
+    /// it sent
+    fn turn(&mut self, slice: usize) -> Vec<Packet> {
+        let mut sent = Vec::new();
+        let mut starved = false;
+        let mut steps = 0;
+
+        while steps < slice {
+            match self.computer.step() {
+                CPUState::AwaitingInstruction => steps += 1,
+                CPUState::AwaitingInput => match self.queue.pop_front() {
+                    Some(packet) => {
+                        self.computer.io.add_input(packet.x);
+                        self.computer.io.add_input(packet.y);
+                        self.log.push(Event::Received(packet));
+                        self.idle_reads = 0;
+                    },
+                    None if !starved => {
+                        self.computer.io.add_input(-1);
+                        self.log.push(Event::Starved);
+                        self.idle_reads += 1;
+                        starved = true;
+                    },
+                    None => break,
+                },
+                CPUState::Halted | CPUState::Faulted(_) => break,
+            }
+
+            let output = &mut self.computer.io.output;
+            while output.len() >= 3 {
+                let triple: Vec<Word> = output.drain(.. 3).collect();
+                let packet = Packet { source: self.address, destination: triple[0], x: triple[1], y: triple[2] };
+                self.log.push(Event::Sent(packet));
+                self.idle_reads = 0;
+                sent.push(packet);
+            }
+        }
+        sent
+    }
+
+    /// Waiting for packets which aren't there, or stopped so it will
+    /// never read any more
+    fn is_idle(&self, threshold: usize) -> bool {
+        match self.computer.cpu_state() {
+            CPUState::Halted | CPUState::Faulted(_) => true,
+            _ => self.queue.is_empty() && self.idle_reads >= threshold,
+        }
+    }
+}
+
+/// Watches address 255, and wakes the network up with the last packet
+/// it saw whenever everything goes idle
+#[derive(Default)]
+pub struct Nat {
+    /// Every packet sent to the NAT
+    pub received: Vec<Packet>,
+    /// Every packet the NAT has sent to address 0
+    pub sent: Vec<Packet>,
+}
+
+impl Nat {
+    /// The first `y` the NAT sent twice in a row
+    pub fn first_repeated_y(&self) -> Option<Word> {
+        self.sent.windows(2).find(|w| w[0].y == w[1].y).map(|w| w[0].y)
+    }
+}
+
+/// A packet switched network of computers, as on day 23. Each computer
+/// is given its address as its first input, then sends packets by
+/// outputting `destination, x, y` and receives them as `x, y`, with -1
+/// meaning nothing is waiting.
+///
+/// Scheduling is deterministic: each round gives every node a turn in
+/// address order, and a node's turn lasts until it asks for input with
+/// nothing queued for the second time, or runs `slice` instructions.
+/// Packets are delivered as soon as they are sent.
+pub struct Network {
+    pub nodes: Vec<Node>,
+    pub nat: Nat,
+    /// Packets sent to addresses with no node behind them
+    pub dropped: Vec<Packet>,
+    /// The most instructions a node runs in one turn
+    pub slice: usize,
+    /// How many times in a row every node must be starved of input
+    /// before the network counts as idle
+    pub idle_threshold: usize,
+}
+
+impl Network {
+    /// Creates a network of `size` computers all running the tape
+    pub fn new(tape: &Tape, size: usize) -> Network {
+        Network {
+            nodes: (0 .. size).map(|a| Node::new(a as Reference, tape)).collect(),
+            nat: Nat::default(),
+            dropped: Vec::new(),
+            slice: 10_000,
+            idle_threshold: 2,
+        }
+    }
+
+    pub fn node(&self, address: Reference) -> Option<&Node> {
+        self.nodes.get(address as usize)
+    }
+
+    /// Every node is waiting for packets with none in flight, or has
+    /// stopped
+    pub fn is_idle(&self) -> bool {
+        self.nodes.iter().all(|n| n.is_idle(self.idle_threshold))
+    }
+
+    /// Gives every node a turn, then lets the NAT wake the network up if
+    /// it has gone idle
+    pub fn round(&mut self) {
+        for i in 0 .. self.nodes.len() {
+            for packet in self.nodes[i].turn(self.slice) {
+                self.deliver(packet);
+            }
+        }
+
+        if self.is_idle() {
+            if let Some(last) = self.nat.received.last() {
+                let packet = Packet { source: NAT_ADDRESS, destination: 0, x: last.x, y: last.y };
+                self.nat.sent.push(packet);
+                self.deliver(packet);
+            }
+        }
+    }
+
+    /// Runs rounds until `done` returns true, giving up after
+    /// `max_rounds`. Returns whether `done` was satisfied.
+    pub fn run_until<F: FnMut(&Network) -> bool>(&mut self, mut done: F, max_rounds: usize) -> bool {
+        for _ in 0 .. max_rounds {
+            if done(self) {
+                return true;
+            }
+            self.round();
+        }
+        done(self)
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        if packet.destination == NAT_ADDRESS {
+            self.nat.received.push(packet);
+        } else if let Some(node) = self.nodes.get_mut(packet.destination as usize).filter(|_| packet.destination >= 0) {
+            node.queue.push_back(packet);
+        } else {
+            self.dropped.push(packet);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    /// Sends (address, address * 10) to the NAT, then sends every packet
+    /// it receives on to the NAT with one added to y
+    const FORWARDER: &str = "3,100,1002,100,10,101,104,255,4,100,4,101,3,102,1008,102,-1,103,1005,103,12,3,104,1001,104,1,104,104,255,4,102,4,104,1105,1,12";
+
+    /// Sends (address, 7) to the NAT, then ignores everything
+    const QUIET: &str = "3,100,104,255,4,100,104,7,3,101,1105,1,8";
+
+    #[test]
+    fn test_nat_wakes_network() {
+        let mut network = Network::new(&FORWARDER.parse().unwrap(), 3);
+        assert!(network.run_until(|n| n.nat.sent.len() == 3, 100));
+
+        assert_eq!(vec![0, 10, 20, 21, 22], network.nat.received.iter().map(|p| p.y).collect::<Vec<Word>>());
+        assert_eq!(vec![20, 21, 22], network.nat.sent.iter().map(|p| p.y).collect::<Vec<Word>>());
+        assert_eq!(None, network.nat.first_repeated_y());
+        assert!(network.dropped.is_empty());
+
+        let sent = |address| network.node(address).unwrap().log.iter().filter(|e| matches!(e, Event::Sent(_))).count();
+        assert_eq!(3, sent(0));
+        assert_eq!(1, sent(1));
+    }
+
+    #[test]
+    fn test_first_repeated_y() {
+        let mut network = Network::new(&QUIET.parse().unwrap(), 4);
+        assert!(network.run_until(|n| n.nat.first_repeated_y().is_some(), 100));
+
+        assert_eq!(Some(7), network.nat.first_repeated_y());
+        assert_eq!(Packet { source: 3, destination: NAT_ADDRESS, x: 3, y: 7 }, network.nat.received[3]);
+        assert_eq!(Packet { source: NAT_ADDRESS, destination: 0, x: 3, y: 7 }, network.nat.sent[0]);
+    }
+
+    #[test]
+    fn test_stopped_node_is_idle() {
+        // Sends (address, 7) to the NAT, except node 1 which halts
+        let tape = compile("
+            fn main() {
+                let address = input();
+                if address == 1 { return; }
+                output(255); output(address); output(7);
+                while 1 { input(); }
+            }").unwrap();
+        let mut network = Network::new(&tape, 2);
+        assert!(network.run_until(|n| !n.nat.sent.is_empty(), 10));
+
+        assert_eq!(CPUState::Halted, network.node(1).unwrap().computer.cpu_state());
+        assert_eq!(Packet { source: NAT_ADDRESS, destination: 0, x: 0, y: 7 }, network.nat.sent[0]);
+    }
+
+    #[test]
+    fn test_node_log() {
+        let mut network = Network::new(&FORWARDER.parse().unwrap(), 2);
+        network.run_until(|n| !n.nat.sent.is_empty(), 100);
+        network.round();
+
+        let nat_packet = Packet { source: NAT_ADDRESS, destination: 0, x: 1, y: 10 };
+        assert_eq!(vec![
+            Event::Sent(Packet { source: 0, destination: NAT_ADDRESS, x: 0, y: 0 }),
+            Event::Starved,
+            Event::Starved,
+            Event::Received(nat_packet),
+            Event::Sent(Packet { source: 0, destination: NAT_ADDRESS, x: 1, y: 11 }),
+            Event::Starved,
+        ], network.node(0).unwrap().log);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let run = || {
+            let mut network = Network::new(&FORWARDER.parse().unwrap(), 5);
+            network.slice = 3;
+            network.run_until(|n| n.nat.sent.len() == 4, 200);
+            network.nodes.iter().map(|n| n.log.clone()).collect::<Vec<Vec<Event>>>()
+        };
+        assert_eq!(run(), run());
+    }
+}
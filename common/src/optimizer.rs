@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::computer::{DecodedInstruction, OpCode, ParameterMode, Reference, Tape, Word};
+use crate::instruction_set::{InstructionSet, Role};
+
+/// Reasons a tape can't be safely optimized. Anything which would make
+/// the new layout of memory observable to the program is refused.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum OptimizeError {
+    /// The instruction at `address` isn't valid, or runs off the tape
+    InvalidInstruction { address: Reference },
+    /// The instruction at `address` uses relative mode, so the locations
+    /// it touches aren't known
+    RelativeMode { address: Reference },
+    /// The instruction at `address` reads or writes through a pointer
+    /// the program changes
+    DynamicAddress { address: Reference },
+    /// The jump at `address` goes somewhere the program works out
+    DynamicJump { address: Reference },
+    /// The program writes to the instruction at `address`
+    SelfModifying { address: Reference },
+    /// The instruction at `address` overlaps another instruction
+    OverlappingCode { address: Reference },
+}
+
+/// The result of optimizing a tape
+#[derive(Debug)]
+pub struct Optimized {
+    pub tape: Tape,
+    /// Where each location kept from the old tape ended up
+    pub mapping: BTreeMap<Reference, Reference>,
+    /// The number of instructions folded or removed
+    pub folded: usize,
+}
+
+/// Shrinks tapes by constant propagation and dead code removal.
+///
+/// Parameters which read locations the program never writes are replaced
+/// with their values, instructions working only on constants are folded,
+/// jumps which can never be taken are removed, and anything which can't
+/// be reached from location 0 or is no longer referenced is dropped.
+///
+/// ```
+/// use common::computer::{Computer, Tape};
+/// use common::optimizer::Optimizer;
+///
+/// let tape: Tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+/// let optimized = Optimizer::new().optimize(&tape).unwrap();
+/// assert_eq!(vec![3, 9, 1008, 9, 8, 9, 4, 9, 99, -1], optimized.tape.contents);
+///
+/// let mut computer = Computer::new_with_tape(&optimized.tape);
+/// computer.io.add_input(8);
+/// computer.run();
+/// assert_eq!(vec![1], computer.io.output);
+/// ```
+#[derive(Default)]
+pub struct Optimizer {
+    /// Locations which may be patched before the program runs, so must
+    /// not be treated as constants
+    pub inputs: Vec<Reference>,
+}
+
+/// An instruction found by following the program from location 0
+#[derive(Clone, Debug)]
+struct Node {
+    address: Reference,
+    opcode: OpCode,
+    modes: Vec<ParameterMode>,
+    roles: &'static [Role],
+    parameters: Vec<Word>,
+}
+
+impl Node {
+    fn len(&self) -> Reference {
+        1 + self.parameters.len() as Reference
+    }
+
+    fn cells(&self) -> std::ops::Range<Reference> {
+        self.address .. self.address + self.len()
+    }
+
+    /// The locations the instruction writes to
+    fn writes(&self) -> Vec<Reference> {
+        self.roles.iter().enumerate()
+            .filter(|(_, role)| **role == Role::Write)
+            .map(|(i, _)| match self.modes[i] {
+                ParameterMode::Immediate => self.address + 1 + i as Reference,
+                _ => self.parameters[i],
+            })
+            .collect()
+    }
+
+    fn encode(&self) -> Vec<Word> {
+        let modes = self.modes.iter().enumerate()
+            .map(|(i, mode)| mode_digit(*mode) * 10_i64.pow(i as u32 + 2))
+            .sum::<Word>();
+        let mut words = vec![self.opcode as Word + modes];
+        words.extend(&self.parameters);
+        words
+    }
+}
+
+fn mode_digit(mode: ParameterMode) -> Word {
+    match mode {
+        ParameterMode::Position => 0,
+        ParameterMode::Immediate => 1,
+        ParameterMode::Relative => 2,
+    }
+}
+
+/// What the analysis knows about the program
+struct Analysis<'a> {
+    tape: &'a Tape,
+    written: HashSet<Reference>,
+}
+
+impl<'a> Analysis<'a> {
+    /// The value a parameter is known to have, if it's a constant
+    fn constant(&self, node: &Node, parameter: usize) -> Option<Word> {
+        let value = node.parameters[parameter];
+        match node.modes[parameter] {
+            ParameterMode::Immediate => Some(value),
+            ParameterMode::Position if value >= 0 && !self.written.contains(&value) => {
+                Some(self.tape.contents.get(value as usize).cloned().unwrap_or(0))
+            },
+            _ => None,
+        }
+    }
+
+    fn decode(&self, address: Reference, instruction_set: &InstructionSet) -> Result<Node, OptimizeError> {
+        let invalid = OptimizeError::InvalidInstruction { address };
+        let word = *self.tape.contents.get(address as usize).ok_or(invalid)?;
+        let op = DecodedInstruction::from_word(word, instruction_set).ok_or(invalid)?;
+        let opcode = OpCode::from_word(op.instruction.opcode).ok_or(invalid)?;
+
+        let count = op.instruction.parameters.len();
+        if address as usize + count >= self.tape.contents.len() {
+            return Err(invalid);
+        }
+
+        let modes: Vec<ParameterMode> = (0 .. count).map(|i| op.modes.mode(i)).collect();
+        if opcode == OpCode::AdjustRelativeBase || modes.contains(&ParameterMode::Relative) {
+            return Err(OptimizeError::RelativeMode { address });
+        }
+
+        let parameters = self.tape.contents[address as usize + 1 ..= address as usize + count].to_vec();
+        if modes.iter().zip(&parameters).any(|(m, p)| *m == ParameterMode::Position && *p < 0) {
+            return Err(invalid);
+        }
+
+        Ok(Node { address, opcode, modes, roles: op.instruction.parameters, parameters })
+    }
+
+    /// Follows the program from location 0, returning every instruction
+    /// which can be reached
+    fn reachable(&self) -> Result<BTreeMap<Reference, Node>, OptimizeError> {
+        let instruction_set = InstructionSet::intcode();
+        let mut nodes = BTreeMap::new();
+        let mut pending = vec![0];
+
+        let mut written = HashSet::new();
+
+        while let Some(address) = pending.pop() {
+            if nodes.contains_key(&address) {
+                continue;
+            }
+            let node = match self.decode(address, &instruction_set) {
+                Ok(node) => node,
+                // Presumably the program writes the real instruction first
+                Err(_) if self.written.contains(&address) || written.contains(&address) => {
+                    return Err(OptimizeError::SelfModifying { address });
+                },
+                Err(e) => return Err(e),
+            };
+            written.extend(node.writes());
+            let next = address + node.len();
+
+            match node.opcode {
+                OpCode::Halt => {},
+                OpCode::JumpIfNotZero | OpCode::JumpIfZero => {
+                    let target = self.constant(&node, 1).ok_or(OptimizeError::DynamicJump { address })?;
+                    match self.constant(&node, 0) {
+                        Some(condition) if (condition != 0) == (node.opcode == OpCode::JumpIfNotZero) => pending.push(target),
+                        Some(_) => pending.push(next),
+                        None => {
+                            pending.push(target);
+                            pending.push(next);
+                        },
+                    }
+                },
+                _ => pending.push(next),
+            }
+
+            nodes.insert(address, node);
+        }
+
+        Ok(nodes)
+    }
+}
+
+impl Optimizer {
+    pub fn new() -> Optimizer {
+        Optimizer { inputs: Vec::new() }
+    }
+
+    pub fn optimize(&self, tape: &Tape) -> Result<Optimized, OptimizeError> {
+        let (analysis, nodes) = self.analyse(tape)?;
+        check_code(&analysis, &nodes)?;
+
+        // The first instruction may be overwritten once it has run, in which
+        // case it has to stay exactly as it is
+        let entry_written = nodes.get(&0).is_some_and(|n| n.cells().any(|c| analysis.written.contains(&c)));
+
+        let mut folded = 0;
+        let mut kept = Vec::new();
+        for node in nodes.values() {
+            if node.address == 0 && entry_written {
+                kept.push(node.clone());
+                continue;
+            }
+            match fold(&analysis, node) {
+                Some(new_node) => {
+                    if new_node.encode() != node.encode() {
+                        folded += 1;
+                    }
+                    kept.push(new_node);
+                },
+                None => folded += 1,
+            }
+        }
+
+        // Work out which locations survive
+        let mut cells = BTreeSet::new();
+        for node in &kept {
+            cells.extend(node.cells());
+            for (i, parameter) in node.parameters.iter().enumerate() {
+                if node.modes[i] == ParameterMode::Position && *parameter < tape.contents.len() as Reference {
+                    cells.insert(*parameter);
+                }
+            }
+        }
+        let mapping: BTreeMap<Reference, Reference> = cells.iter().enumerate()
+            .map(|(new, old)| (*old, new as Reference))
+            .collect();
+        let relocate = |old: Reference| -> Reference {
+            if old >= tape.contents.len() as Reference {
+                return old;
+            }
+            // Jumps to removed instructions go to whatever followed them
+            mapping.range(old ..).next().map_or(cells.len() as Reference, |(_, new)| *new)
+        };
+
+        let mut contents: Vec<Word> = cells.iter().map(|c| tape.contents[*c as usize]).collect();
+        for mut node in kept {
+            let verbatim = node.address == 0 && entry_written;
+            for i in 0 .. node.parameters.len() {
+                if node.modes[i] == ParameterMode::Position || is_jump_target(&node, i) {
+                    let relocated = relocate(node.parameters[i]);
+                    if verbatim && relocated != node.parameters[i] {
+                        return Err(OptimizeError::SelfModifying { address: 0 });
+                    }
+                    node.parameters[i] = relocated;
+                }
+            }
+            let start = mapping[&node.address] as usize;
+            contents.splice(start .. start + node.len() as usize, node.encode());
+        }
+
+        Ok(Optimized { tape: Tape { contents }, mapping, folded })
+    }
+
+    /// Finds the reachable instructions and the locations they write,
+    /// repeating until the two agree
+    fn analyse<'a>(&self, tape: &'a Tape) -> Result<(Analysis<'a>, BTreeMap<Reference, Node>), OptimizeError> {
+        let inputs: HashSet<Reference> = self.inputs.iter().cloned().collect();
+        let mut analysis = Analysis { tape, written: inputs.clone() };
+
+        loop {
+            let nodes = analysis.reachable()?;
+
+            let mut written = inputs.clone();
+            for node in nodes.values() {
+                written.extend(node.writes());
+            }
+
+            if written == analysis.written {
+                return Ok((analysis, nodes));
+            }
+            analysis.written = written;
+        }
+    }
+}
+
+/// Checks every address the program uses is fixed and that it never
+/// writes to its own instructions, other than the first one
+fn check_code(analysis: &Analysis, nodes: &BTreeMap<Reference, Node>) -> Result<(), OptimizeError> {
+    let mut end = 0;
+    for node in nodes.values() {
+        if node.address < end {
+            return Err(OptimizeError::OverlappingCode { address: node.address });
+        }
+        end = node.address + node.len();
+
+        // The first instruction runs before anything is written, so only
+        // matters if it can be run again
+        let entry = node.address == 0 && !nodes.values().any(|n| jumps_to(analysis, n, 0));
+        if entry {
+            continue;
+        }
+
+        for i in 0 .. node.parameters.len() {
+            let slot = node.address + 1 + i as Reference;
+            if node.modes[i] == ParameterMode::Position && analysis.written.contains(&slot) {
+                return Err(OptimizeError::DynamicAddress { address: node.address });
+            }
+        }
+        if node.cells().any(|c| analysis.written.contains(&c)) {
+            return Err(OptimizeError::SelfModifying { address: node.address });
+        }
+    }
+    Ok(())
+}
+
+fn jumps_to(analysis: &Analysis, node: &Node, address: Reference) -> bool {
+    (node.opcode == OpCode::JumpIfNotZero || node.opcode == OpCode::JumpIfZero) && analysis.constant(node, 1) == Some(address)
+}
+
+fn is_jump_target(node: &Node, parameter: usize) -> bool {
+    (node.opcode == OpCode::JumpIfNotZero || node.opcode == OpCode::JumpIfZero) && parameter == 1
+}
+
+/// Rewrites an instruction using what's known to be constant, returning
+/// `None` if it can be removed altogether
+fn fold(analysis: &Analysis, node: &Node) -> Option<Node> {
+    let mut node = node.clone();
+
+    // Turn reads of constants into immediate values, including jump
+    // targets so the location holding them is no longer needed
+    for i in 0 .. node.parameters.len() {
+        if node.roles[i] == Role::Read {
+            if let Some(value) = analysis.constant(&node, i) {
+                node.parameters[i] = value;
+                node.modes[i] = ParameterMode::Immediate;
+            }
+        }
+    }
+
+    let immediate = |node: &Node, i: usize| if node.modes[i] == ParameterMode::Immediate { Some(node.parameters[i]) } else { None };
+
+    match node.opcode {
+        OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equal => {
+            if let (Some(a), Some(b)) = (immediate(&node, 0), immediate(&node, 1)) {
+                let result = match node.opcode {
+                    OpCode::Add => a.checked_add(b),
+                    OpCode::Mul => a.checked_mul(b),
+                    OpCode::LessThan => Some((a < b) as Word),
+                    _ => Some((a == b) as Word),
+                };
+                if let Some(result) = result {
+                    node.opcode = OpCode::Add;
+                    node.parameters[0] = result;
+                    node.parameters[1] = 0;
+                }
+            }
+        },
+        OpCode::JumpIfNotZero | OpCode::JumpIfZero => {
+            if let Some(condition) = immediate(&node, 0) {
+                if (condition != 0) != (node.opcode == OpCode::JumpIfNotZero) {
+                    return None;
+                }
+                node.opcode = OpCode::JumpIfNotZero;
+                node.parameters[0] = 1;
+            }
+        },
+        _ => {},
+    }
+
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer::{Computer, CPUState};
+    use super::*;
+
+    /// Runs both tapes with each input and checks they behave the same
+    fn assert_equivalent(tape: &str, inputs: &[Word]) -> Optimized {
+        let tape: Tape = tape.parse().unwrap();
+        let optimized = Optimizer::new().optimize(&tape).unwrap();
+        assert!(optimized.tape.contents.len() <= tape.contents.len());
+
+        for input in inputs {
+            let mut original = Computer::new_with_tape(&tape);
+            original.io.add_input(*input);
+            original.run();
+
+            let mut subject = Computer::new_with_tape(&optimized.tape);
+            subject.io.add_input(*input);
+            subject.run();
+
+            assert_eq!(CPUState::Halted, subject.cpu_state());
+            assert_eq!(original.io.output, subject.io.output, "input {}", input);
+        }
+        optimized
+    }
+
+    #[test]
+    fn test_day_5_vectors() {
+        assert_equivalent("3,9,8,9,10,9,4,9,99,-1,8", &[1, 8]);
+        assert_equivalent("3,9,7,9,10,9,4,9,99,-1,8", &[1, 100]);
+        assert_equivalent("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0, 800]);
+        assert_equivalent("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[0, 8, 800]);
+    }
+
+    #[test]
+    fn test_jump_folding() {
+        let optimized = assert_equivalent("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0, 800]);
+        assert_eq!(vec![3, 12, 1006, 12, 9, 1001, 13, 1, 13, 4, 13, 99, -1, 0], optimized.tape.contents);
+    }
+
+    #[test]
+    fn test_dead_code() {
+        // Jumps over a block which is never executed
+        let optimized = assert_equivalent("1105,1,7,104,1,104,2,104,3,99", &[0]);
+        assert_eq!(vec![1105, 1, 3, 104, 3, 99], optimized.tape.contents);
+        assert_eq!(Some(&3), optimized.mapping.get(&7));
+        assert_eq!(None, optimized.mapping.get(&3));
+    }
+
+    #[test]
+    fn test_constant_arithmetic() {
+        let optimized = assert_equivalent("1,9,10,11,4,11,99,0,0,6,7,0", &[0]);
+        assert_eq!(vec![1101, 13, 0, 7, 4, 7, 99, 0], optimized.tape.contents);
+        assert_eq!(1, optimized.folded);
+    }
+
+    #[test]
+    fn test_never_taken_jump_removed() {
+        let optimized = assert_equivalent("1106,1,6,104,1,99,104,2,99", &[0]);
+        assert_eq!(vec![104, 1, 99], optimized.tape.contents);
+    }
+
+    #[test]
+    fn test_day_2_entry_overwritten() {
+        let tape: Tape = "1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap();
+        let optimized = Optimizer::new().optimize(&tape).unwrap();
+
+        let mut computer = Computer::new_with_tape(&optimized.tape);
+        assert_eq!(3500, computer.run());
+    }
+
+    #[test]
+    fn test_refuses() {
+        let optimize = |s: &str| Optimizer::new().optimize(&s.parse().unwrap()).err();
+        assert_eq!(Some(OptimizeError::SelfModifying { address: 4 }), optimize("1002,4,3,4,33"));
+        assert_eq!(Some(OptimizeError::RelativeMode { address: 0 }), optimize("109,1,204,-1,99"));
+        assert_eq!(Some(OptimizeError::InvalidInstruction { address: 2 }), optimize("104,1"));
+        // Reads its input straight into the next instruction
+        assert_eq!(Some(OptimizeError::SelfModifying { address: 2 }), optimize("3,3,1108,-1,8,3,4,3,99"));
+
+        let mut optimizer = Optimizer::new();
+        optimizer.inputs = vec![6];
+        assert_eq!(Some(OptimizeError::DynamicJump { address: 0 }), optimizer.optimize(&"6,7,6,99,104,1,4,0".parse().unwrap()).err());
+    }
+
+    #[test]
+    fn test_day_5_input_is_self_modifying() {
+        let tape: Tape = std::fs::read_to_string("../day-05/input.txt").unwrap().parse().unwrap();
+        assert_eq!(Some(OptimizeError::SelfModifying { address: 6 }), Optimizer::new().optimize(&tape).err());
+    }
+}
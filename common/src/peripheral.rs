@@ -0,0 +1,312 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::computer::{Computer, CPUState, Tape, Word};
+use crate::grid::{Direction, Grid, Point};
+
+/// A device driven by a program through its output, which can also
+/// answer when the program asks for input
+pub trait Peripheral {
+    /// Takes a word the program output
+    fn output(&mut self, value: Word);
+
+    /// A word to give the program, or `None` if the device has nothing to
+    /// say
+    fn input(&mut self) -> Option<Word>;
+}
+
+/// Lets a device be attached to a bus while still being looked at from
+/// outside, such as a screen a joystick's strategy watches
+impl<P: Peripheral + ?Sized> Peripheral for Rc<RefCell<P>> {
+    fn output(&mut self, value: Word) {
+        self.borrow_mut().output(value)
+    }
+
+    fn input(&mut self) -> Option<Word> {
+        self.borrow_mut().input()
+    }
+}
+
+/// Connects a computer to a set of devices. Every word the program
+/// outputs is given to each device in turn, and when the program asks for
+/// input the devices are asked in the order they were added until one
+/// answers.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use common::peripheral::{Bus, Screen};
+///
+/// // Draws tile 2 at (3, 4) and sets the score to 100
+/// let mut bus = Bus::new(&"104,3,104,4,104,2,104,-1,104,0,104,100,99".parse().unwrap());
+/// let screen = Rc::new(RefCell::new(Screen::default()));
+/// bus.attach(screen.clone());
+/// bus.run();
+///
+/// assert_eq!(Some(2), screen.borrow().tile(3, 4));
+/// assert_eq!(100, screen.borrow().score);
+/// ```
+pub struct Bus {
+    pub computer: Computer,
+    devices: Vec<Box<dyn Peripheral>>,
+}
+
+impl Bus {
+    pub fn new(tape: &Tape) -> Bus {
+        Bus::with_computer(Computer::new_with_tape(tape))
+    }
+
+    pub fn with_computer(computer: Computer) -> Bus {
+        Bus { computer, devices: Vec::new() }
+    }
+
+    pub fn attach<P: Peripheral + 'static>(&mut self, device: P) {
+        self.devices.push(Box::new(device));
+    }
+
+    /// Runs the computer until it stops, or asks for input which no device
+    /// will give. The computer runs until it pauses for input, then its
+    /// output is given to the devices before they're asked for input, so
+    /// `computer.io.output` is left empty.
+    pub fn run(&mut self) -> CPUState {
+        loop {
+            self.computer.run();
+            for value in self.computer.io.output.drain(..) {
+                for device in self.devices.iter_mut() {
+                    device.output(value);
+                }
+            }
+
+            let state = self.computer.cpu_state();
+            if state != CPUState::AwaitingInput {
+                return state;
+            }
+            match self.devices.iter_mut().find_map(|device| device.input()) {
+                Some(value) => self.computer.io.add_input(value),
+                None => return state,
+            }
+        }
+    }
+}
+
+/// A robot which paints the panel it's on, as on day 11. The program is
+/// given the colour of the panel under the robot, and outputs pairs of the
+/// colour to paint it and which way to turn, 0 for left and 1 for right,
+/// before the robot moves forward a panel.
+#[derive(Clone, Debug)]
+pub struct Robot {
+    pub position: Point,
+    pub facing: Direction,
+    /// The colour of each panel which has been painted, or was given a
+    /// colour to start with
+    pub panels: Grid<Word>,
+    /// Every panel painted at least once
+    pub painted: HashSet<Point>,
+    /// The first half of an output pair
+    paint: Option<Word>,
+}
+
+impl Robot {
+    /// A robot at the origin facing up, where every panel is colour 0
+    pub fn new() -> Robot {
+        Robot { position: Point::default(), facing: Direction::Up, panels: Grid::new(), painted: HashSet::new(), paint: None }
+    }
+
+    pub fn colour(&self, point: Point) -> Word {
+        self.panels.get(point).cloned().unwrap_or(0)
+    }
+}
+
+impl Default for Robot {
+    fn default() -> Self {
+        Robot::new()
+    }
+}
+
+impl Peripheral for Robot {
+    fn output(&mut self, value: Word) {
+        match self.paint.take() {
+            None => self.paint = Some(value),
+            Some(colour) => {
+                self.panels.insert(self.position, colour);
+                self.painted.insert(self.position);
+                self.facing = match value {
+                    0 => self.facing.turn_left(),
+                    _ => self.facing.turn_right(),
+                };
+                self.position = self.position.step(self.facing);
+            },
+        }
+    }
+
+    fn input(&mut self) -> Option<Word> {
+        Some(self.colour(self.position))
+    }
+}
+
+/// A screen drawn with `x, y, tile` triples, as on day 13. The triple
+/// `-1, 0, score` sets the score instead of drawing.
+#[derive(Clone, Debug, Default)]
+pub struct Screen {
+    pub tiles: Grid<Word>,
+    pub score: Word,
+    /// The start of a triple
+    pending: Vec<Word>,
+}
+
+impl Screen {
+    pub fn tile(&self, x: Word, y: Word) -> Option<Word> {
+        self.tiles.get(Point::new(x, y)).cloned()
+    }
+
+    /// Where a tile is drawn, if it's anywhere. If it's drawn in several
+    /// places, any one of them.
+    pub fn find(&self, tile: Word) -> Option<Point> {
+        self.tiles.iter().find(|(_, t)| **t == tile).map(|(point, _)| point)
+    }
+
+    /// How many places a tile is drawn
+    pub fn count(&self, tile: Word) -> usize {
+        self.tiles.iter().filter(|(_, t)| **t == tile).count()
+    }
+}
+
+impl Peripheral for Screen {
+    fn output(&mut self, value: Word) {
+        self.pending.push(value);
+        if let [x, y, tile] = self.pending[..] {
+            self.pending.clear();
+            if (x, y) == (-1, 0) {
+                self.score = tile;
+            } else {
+                self.tiles.insert(Point::new(x, y), tile);
+            }
+        }
+    }
+
+    fn input(&mut self) -> Option<Word> {
+        None
+    }
+}
+
+/// A joystick whose position is chosen by a strategy each time the
+/// program reads it: -1 for left, 0 for neutral and 1 for right on day 13
+pub struct Joystick {
+    strategy: Box<dyn FnMut() -> Word>,
+    /// How many times the joystick has been read
+    pub reads: usize,
+}
+
+impl Joystick {
+    pub fn new<F: FnMut() -> Word + 'static>(strategy: F) -> Joystick {
+        Joystick { strategy: Box::new(strategy), reads: 0 }
+    }
+}
+
+impl Peripheral for Joystick {
+    fn output(&mut self, _: Word) {}
+
+    fn input(&mut self) -> Option<Word> {
+        self.reads += 1;
+        Some((self.strategy)())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+
+    #[test]
+    fn test_robot() {
+        // The day 11 example: paint and turn as given, ignoring the input
+        let tape = compile("
+            fn step(colour, turn) {
+                input();
+                output(colour);
+                output(turn);
+            }
+            fn main() {
+                step(1, 0); step(0, 0); step(1, 0); step(1, 0);
+                step(0, 1); step(1, 0); step(1, 0);
+            }").unwrap();
+        let robot = Rc::new(RefCell::new(Robot::new()));
+        let mut bus = Bus::new(&tape);
+        bus.attach(robot.clone());
+        assert_eq!(CPUState::Halted, bus.run());
+
+        let robot = robot.borrow();
+        assert_eq!(6, robot.painted.len());
+        assert_eq!(Point::new(0, -1), robot.position);
+        assert_eq!(Direction::Left, robot.facing);
+        assert_eq!(0, robot.colour(Point::new(0, 0)));
+        assert_eq!(1, robot.colour(Point::new(1, 0)));
+    }
+
+    #[test]
+    fn test_robot_input() {
+        // Paints each panel one more than its colour, turning right, so
+        // goes round a square back to the start
+        let tape = compile("
+            fn main() {
+                let i = 0;
+                while i < 4 {
+                    output(input() + 1);
+                    output(1);
+                    i = i + 1;
+                }
+            }").unwrap();
+        let mut robot = Robot::new();
+        robot.panels.insert(Point::new(0, 0), 10);
+        let robot = Rc::new(RefCell::new(robot));
+        let mut bus = Bus::new(&tape);
+        bus.attach(robot.clone());
+        assert_eq!(CPUState::Halted, bus.run());
+
+        let robot = robot.borrow();
+        assert_eq!(11, robot.colour(Point::new(0, 0)));
+        assert_eq!(1, robot.colour(Point::new(1, 1)));
+        assert_eq!(4, robot.painted.len());
+        assert_eq!((Point::new(0, 0), Direction::Up), (robot.position, robot.facing));
+    }
+
+    #[test]
+    fn test_screen_and_joystick() {
+        // Reads the joystick three times, drawing a tile at the position
+        // it was moved to each time and scoring the total
+        let tape = compile("
+            fn main() {
+                let x = 0;
+                let reads = 0;
+                while reads < 3 {
+                    x = x + input();
+                    output(x); output(0); output(4);
+                    reads = reads + 1;
+                }
+                output(-1); output(0); output(x * 100);
+            }").unwrap();
+        let screen = Rc::new(RefCell::new(Screen::default()));
+        let moves = vec![1, 1, -1];
+        let mut next = moves.into_iter();
+
+        let mut bus = Bus::new(&tape);
+        bus.attach(screen.clone());
+        bus.attach(Joystick::new(move || next.next().unwrap()));
+        assert_eq!(CPUState::Halted, bus.run());
+
+        let screen = screen.borrow();
+        assert_eq!(2, screen.count(4));
+        assert_eq!(Some(4), screen.tile(2, 0));
+        assert_eq!(None, screen.tile(0, 0));
+        assert_eq!(100, screen.score);
+        assert!(bus.computer.io.output.is_empty());
+    }
+
+    #[test]
+    fn test_unanswered_input() {
+        let mut bus = Bus::new(&"3,0,99".parse().unwrap());
+        bus.attach(Screen::default());
+        assert_eq!(CPUState::AwaitingInput, bus.run());
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use crate::computer::{Access, Reference};
+
+/// What is checked for a protected region of memory
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Protection {
+    /// Instructions may not write to the region
+    ReadOnly,
+    /// Instructions may not be executed from the region
+    NoExecute,
+    /// Any data read or write is reported, like a data breakpoint
+    Watch,
+}
+
+impl Protection {
+    fn violated_by(self, access: Access) -> bool {
+        match self {
+            Protection::ReadOnly => access == Access::Write,
+            Protection::NoExecute => access == Access::Fetch || access == Access::Operand,
+            Protection::Watch => access == Access::Read || access == Access::Write,
+        }
+    }
+}
+
+/// A range of memory locations with a protection applied
+#[derive(PartialEq, Clone, Debug)]
+pub struct Region {
+    pub range: Range<Reference>,
+    pub protection: Protection,
+}
+
+/// An access which tripped a protected region
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Violation {
+    pub protection: Protection,
+    pub access: Access,
+    /// The location which was accessed
+    pub location: Reference,
+    /// The address of the instruction making the access
+    pub instruction: Reference,
+}
+
+/// Decides whether a violation should fault the computer
+pub type Handler = Box<dyn FnMut(&Violation) -> bool>;
+
+/// Protected regions of memory, set up with `Memory::protect`.
+///
+/// Every violation is logged. Without a handler, `ReadOnly` and
+/// `NoExecute` violations fault the computer while `Watch` violations
+/// only get logged. A handler replaces that decision: it is called for
+/// every violation and the computer faults if it returns true.
+///
+/// ```
+/// use common::computer::{Computer, CPUState, Fault};
+/// use common::protection::Protection;
+///
+/// let mut computer = Computer::new_with_tape(&"1002,4,3,4,33".parse().unwrap());
+/// computer.memory.protect(0 .. 5, Protection::ReadOnly);
+/// computer.run();
+///
+/// match computer.cpu_state() {
+///     CPUState::Faulted(Fault::Protection(violation)) => assert_eq!(4, violation.location),
+///     _ => panic!("expected a fault"),
+/// }
+/// ```
+#[derive(Default)]
+pub struct MemoryProtection {
+    pub regions: Vec<Region>,
+    /// Every violation so far, in the order it happened
+    pub violations: Vec<Violation>,
+
+    handler: Option<Handler>,
+}
+
+impl MemoryProtection {
+    pub fn new() -> MemoryProtection {
+        MemoryProtection { regions: Vec::new(), violations: Vec::new(), handler: None }
+    }
+
+    pub fn add(&mut self, range: Range<Reference>, protection: Protection) {
+        self.regions.push(Region { range, protection });
+    }
+
+    pub fn set_handler(&mut self, handler: Handler) {
+        self.handler = Some(handler);
+    }
+
+    /// Checks an access against every region, returning the first
+    /// violation which should fault the computer
+    pub(crate) fn check(&mut self, access: Access, location: Reference, instruction: Reference) -> Option<Violation> {
+        let violations: Vec<Violation> = self.regions.iter()
+            .filter(|r| r.range.contains(&location) && r.protection.violated_by(access))
+            .map(|r| Violation { protection: r.protection, access, location, instruction })
+            .collect();
+
+        let mut fault = None;
+        for violation in violations {
+            self.violations.push(violation);
+
+            let faults = match self.handler.as_mut() {
+                Some(handler) => handler(&violation),
+                None => violation.protection != Protection::Watch,
+            };
+            if faults && fault.is_none() {
+                fault = Some(violation);
+            }
+        }
+        fault
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::computer::{Computer, CPUState, Fault};
+    use super::*;
+
+    #[test]
+    fn test_read_only_blocks_write() {
+        let mut computer = Computer::new_with_tape(&"1101,1,2,5,99,0".parse().unwrap());
+        computer.memory.protect(5 .. 6, Protection::ReadOnly);
+        computer.run();
+
+        assert_eq!(CPUState::Faulted(Fault::Protection(Violation {
+            protection: Protection::ReadOnly, access: Access::Write, location: 5, instruction: 0,
+        })), computer.cpu_state());
+        assert_eq!(0, computer.memory.read_direct(5));
+    }
+
+    #[test]
+    fn test_no_execute() {
+        let mut computer = Computer::new_with_tape(&"1105,1,3,99".parse().unwrap());
+        computer.memory.protect(3 .. 4, Protection::NoExecute);
+        computer.run();
+
+        match computer.cpu_state() {
+            CPUState::Faulted(Fault::Protection(violation)) => {
+                assert_eq!(Access::Fetch, violation.access);
+                assert_eq!(3, violation.instruction);
+            },
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_watch_logs_without_faulting() {
+        let mut computer = Computer::new_with_tape(&"3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap());
+        computer.memory.protect(9 .. 11, Protection::Watch);
+        computer.io.add_input(8);
+        computer.run();
+
+        assert_eq!(CPUState::Halted, computer.cpu_state());
+        let accesses: Vec<(Access, Reference)> = computer.memory.protection.as_ref().unwrap().violations.iter()
+            .map(|v| (v.access, v.location))
+            .collect();
+        assert_eq!(vec![
+            (Access::Write, 9),
+            (Access::Read, 9),
+            (Access::Read, 10),
+            (Access::Write, 9),
+            (Access::Read, 9),
+        ], accesses);
+    }
+
+    #[test]
+    fn test_handler_decides() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = seen.clone();
+
+        let mut computer = Computer::new_with_tape(&"3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap());
+        computer.memory.protect(10 .. 11, Protection::Watch);
+        computer.memory.on_violation(Box::new(move |v| {
+            log.borrow_mut().push(v.instruction);
+            true
+        }));
+        computer.io.add_input(8);
+        computer.run();
+
+        assert_eq!(vec![2], *seen.borrow());
+        match computer.cpu_state() {
+            CPUState::Faulted(Fault::Protection(_)) => {},
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_fault_has_no_side_effects() {
+        let faulting = |tape: &str, location: Reference| {
+            let mut computer = Computer::new_with_tape(&tape.parse().unwrap());
+            computer.memory.protect(location .. location + 1, Protection::Watch);
+            computer.memory.on_violation(Box::new(|_| true));
+            computer.io.add_input(42);
+            computer.run();
+            assert!(matches!(computer.cpu_state(), CPUState::Faulted(_)));
+            computer
+        };
+
+        let computer = faulting("4,5,99,0,0,7", 5);
+        assert!(computer.io.output.is_empty());
+        assert_eq!(0, computer.registers().instruction_pointer);
+
+        let computer = faulting("3,5,99,0,0,0", 5);
+        assert_eq!(vec![42], computer.io.pending_input());
+        assert_eq!(0, computer.memory.read_direct(5));
+
+        let computer = faulting("209,3,99,7", 3);
+        assert_eq!(0, computer.registers().relative_base);
+        assert_eq!(0, computer.registers().steps);
+
+        // Running on doesn't do any of it either
+        let mut computer = faulting("104,1,4,7,99,0,0,8", 7);
+        computer.run();
+        assert_eq!(vec![1], computer.io.output);
+        assert_eq!(2, computer.registers().instruction_pointer);
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::computer::{Computer, CPUState, Word};
+
+/// A value crossing between a program and the outside world. The step
+/// is the number of instructions executed before the one doing the IO.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum IOEvent {
+    Input { step: u64, value: Word },
+    Output { step: u64, value: Word },
+}
+
+impl fmt::Display for IOEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IOEvent::Input { step, value } => write!(f, "{} in {}", step, value),
+            IOEvent::Output { step, value } => write!(f, "{} out {}", step, value),
+        }
+    }
+}
+
+impl FromStr for IOEvent {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(());
+        }
+        let step = parts[0].parse().map_err(|_| ())?;
+        let value = parts[2].parse().map_err(|_| ())?;
+        match parts[1] {
+            "in" => Ok(IOEvent::Input { step, value }),
+            "out" => Ok(IOEvent::Output { step, value }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Every input and output of a run, in order. Enable recording with
+/// `Computer::enable_recording`, then save the recording to replay later.
+///
+/// Recordings are saved one event per line, as the step, `in` or `out`
+/// and the value. Blank lines and lines starting with `#` are ignored.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Recording {
+    pub events: Vec<IOEvent>,
+}
+
+impl Recording {
+    pub fn new() -> Recording {
+        Recording { events: Vec::new() }
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = Word> + '_ {
+        self.events.iter().filter_map(|e| match e {
+            IOEvent::Input { value, .. } => Some(*value),
+            _ => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = Word> + '_ {
+        self.events.iter().filter_map(|e| match e {
+            IOEvent::Output { value, .. } => Some(*value),
+            _ => None,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+        fs::read_to_string(path)?.parse()
+            .map_err(|line| io::Error::new(io::ErrorKind::InvalidData, format!("bad recording on line {}", line)))
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    /// The line number which couldn't be parsed
+    type Err = usize;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let events = s.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| line.parse().map_err(|_| i + 1))
+            .collect::<Result<Vec<IOEvent>, usize>>()?;
+        Ok(Recording { events })
+    }
+}
+
+/// The first place a replay differs from its recording
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Mismatch {
+    /// Event `index` isn't the one recorded
+    Different { index: usize, expected: IOEvent, actual: IOEvent },
+    /// The program produced output after the recording ended
+    Extra { index: usize, actual: IOEvent },
+    /// The program stopped, or wanted input, when the recording has
+    /// event `index` next
+    Missing { index: usize, expected: IOEvent, state: CPUState },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Different { index, expected, actual } => write!(f, "event {}: expected \"{}\", got \"{}\"", index, expected, actual),
+            Mismatch::Extra { index, actual } => write!(f, "event {}: unexpected \"{}\" after the recording ended", index, actual),
+            Mismatch::Missing { index, expected, state } => write!(f, "event {}: expected \"{}\", but the computer was {:?}", index, expected, state),
+        }
+    }
+}
+
+/// Runs the computer, feeding it the recorded input whenever it asks for
+/// some and checking every event against the recording. The replay ends
+/// successfully if the program stops or waits for input once the whole
+/// recording has been matched.
+///
+/// ```
+/// use common::computer::Computer;
+/// use common::recording::{replay, Recording};
+///
+/// let tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+/// let recording: Recording = "0 in 8\n2 out 1\n".parse().unwrap();
+/// assert!(replay(&mut Computer::new_with_tape(&tape), &recording).is_ok());
+///
+/// let recording: Recording = "0 in 7\n2 out 1\n".parse().unwrap();
+/// let mismatch = replay(&mut Computer::new_with_tape(&tape), &recording).unwrap_err();
+/// assert_eq!("event 1: expected \"2 out 1\", got \"2 out 0\"", mismatch.to_string());
+/// ```
+pub fn replay(computer: &mut Computer, recording: &Recording) -> Result<(), Mismatch> {
+    computer.enable_recording();
+    let expected = &recording.events;
+    let mut checked = 0;
+
+    loop {
+        let state = computer.step();
+
+        let actual = &computer.io.recording.as_ref().unwrap().events;
+        while checked < actual.len() {
+            match expected.get(checked) {
+                Some(e) if *e == actual[checked] => {},
+                Some(e) => return Err(Mismatch::Different { index: checked, expected: *e, actual: actual[checked] }),
+                None => return Err(Mismatch::Extra { index: checked, actual: actual[checked] }),
+            }
+            checked += 1;
+        }
+
+        match (state, expected.get(checked)) {
+            (CPUState::AwaitingInstruction, _) => {},
+            (CPUState::AwaitingInput, Some(IOEvent::Input { value, .. })) => computer.io.add_input(*value),
+            (_, Some(e)) => return Err(Mismatch::Missing { index: checked, expected: *e, state }),
+            (_, None) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPARE_TO_8: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+
+    #[test]
+    fn test_records_steps() {
+        let mut computer = Computer::new_with_tape(&"3,0,4,0,3,0,1002,0,2,0,4,0,99".parse().unwrap());
+        computer.enable_recording();
+        computer.io.add_input(5);
+        computer.io.add_input(6);
+        computer.run();
+
+        assert_eq!(vec![
+            IOEvent::Input { step: 0, value: 5 },
+            IOEvent::Output { step: 1, value: 5 },
+            IOEvent::Input { step: 2, value: 6 },
+            IOEvent::Output { step: 4, value: 12 },
+        ], computer.io.recording.as_ref().unwrap().events);
+        assert_eq!(6, computer.steps());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let tape = COMPARE_TO_8.parse().unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        computer.enable_recording();
+        computer.io.add_input(9);
+        computer.run();
+        let recording = computer.io.recording.take().unwrap();
+        assert_eq!(vec![9], recording.inputs().collect::<Vec<Word>>());
+        assert_eq!(vec![1001], recording.outputs().collect::<Vec<Word>>());
+
+        let path = std::env::temp_dir().join(format!("intcode-recording-{}.txt", std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = Recording::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording, loaded);
+        assert_eq!(Ok(()), replay(&mut Computer::new_with_tape(&tape), &loaded));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Ok(Recording { events: vec![IOEvent::Output { step: 3, value: -4 }] }), "# comment\n\n3 out -4\n".parse());
+        assert_eq!(Err(3), "0 in 1\n1 out 2\n2 sideways 3\n".parse::<Recording>());
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let tape = COMPARE_TO_8.parse().unwrap();
+        let run = |recording: &str| replay(&mut Computer::new_with_tape(&tape), &recording.parse().unwrap());
+
+        // Different step for the same output
+        assert_eq!(Err(Mismatch::Different {
+            index: 1,
+            expected: IOEvent::Output { step: 7, value: 999 },
+            actual: IOEvent::Output { step: 5, value: 999 },
+        }), run("0 in 7\n7 out 999"));
+
+        assert_eq!(Err(Mismatch::Extra { index: 1, actual: IOEvent::Output { step: 5, value: 999 } }), run("0 in 7"));
+        assert_eq!(Err(Mismatch::Missing {
+            index: 2,
+            expected: IOEvent::Input { step: 9, value: 1 },
+            state: CPUState::Halted,
+        }), run("0 in 7\n5 out 999\n9 in 1"));
+
+        // The session ended while the program was waiting for input
+        assert_eq!(Ok(()), run(""));
+    }
+}
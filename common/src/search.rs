@@ -0,0 +1,290 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+use crate::computer::{Computer, CPUState, Reference, Tape, Word};
+
+/// One point in a search space: the words to patch into the tape and the
+/// input to give it
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Candidate {
+    pub patches: Vec<(Reference, Word)>,
+    pub input: Vec<Word>,
+}
+
+impl Candidate {
+    /// A computer with the tape loaded, the patches applied and the input
+    /// queued, ready to run
+    pub fn computer(&self, tape: &Tape) -> Computer {
+        let mut computer = Computer::new_with_tape(tape);
+        for (location, value) in &self.patches {
+            computer.memory.write_direct(*location, *value);
+        }
+        for value in &self.input {
+            computer.io.add_input(*value);
+        }
+        computer
+    }
+}
+
+/// Every combination of values for a set of memory locations, each tried
+/// with every one of a set of inputs. Candidates are evaluated in parallel
+/// with each one run on its own computer.
+///
+/// ```
+/// use common::search::Space;
+///
+/// // Outputs the sum of two inputs
+/// let tape = "3,11,3,12,1,11,12,13,4,13,99,0,0,0".parse().unwrap();
+/// let mut space = Space::new(&tape);
+/// space.add_permutations(&[1, 20, 300]);
+///
+/// let (candidate, output) = space.best(|c| c.io.output.first().cloned(), None).unwrap();
+/// assert_eq!(320, output);
+/// assert_eq!(vec![20, 300, 1], candidate.input);
+/// ```
+pub struct Space {
+    pub tape: Tape,
+    /// Locations and the values to try at each
+    pub patches: Vec<(Reference, Range<Word>)>,
+    /// The inputs to try. With none, every candidate has an empty input.
+    pub inputs: Vec<Vec<Word>>,
+    /// The most instructions to execute for each run
+    pub max_steps: u64,
+}
+
+impl Space {
+    pub fn new(tape: &Tape) -> Space {
+        Space { tape: tape.clone(), patches: Vec::new(), inputs: Vec::new(), max_steps: 1_000_000 }
+    }
+
+    /// Tries every value in the range at a location
+    pub fn add_patch(&mut self, location: Reference, range: Range<Word>) {
+        self.patches.push((location, range));
+    }
+
+    pub fn add_input(&mut self, input: Vec<Word>) {
+        self.inputs.push(input);
+    }
+
+    /// Adds every ordering of the values as an input, such as the phase
+    /// settings on day 7
+    pub fn add_permutations(&mut self, values: &[Word]) {
+        let mut values = values.to_vec();
+        permute(&mut values, 0, &mut self.inputs);
+    }
+
+    /// The number of candidates. Panics if there are too many to count,
+    /// which would be far too many to search anyway.
+    pub fn len(&self) -> usize {
+        self.checked_len().expect("too many candidates to count")
+    }
+
+    /// The number of candidates, or `None` if there are more than fit in
+    /// a `usize`
+    pub fn checked_len(&self) -> Option<usize> {
+        if self.patches.iter().any(|(_, range)| range.is_empty()) {
+            return Some(0);
+        }
+        self.patches.iter().try_fold(self.inputs.len().max(1), |total, (_, range)| total.checked_mul(width(range)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checked_len() == Some(0)
+    }
+
+    /// The candidate at an index from 0 to `len()`. The first location's
+    /// value changes slowest and the input fastest.
+    pub fn candidate(&self, index: usize) -> Candidate {
+        let mut index = index;
+        let input = match self.inputs.len() {
+            0 => Vec::new(),
+            n => {
+                let input = self.inputs[index % n].clone();
+                index /= n;
+                input
+            },
+        };
+
+        let mut patches = Vec::with_capacity(self.patches.len());
+        for (location, range) in self.patches.iter().rev() {
+            let n = width(range).expect("too many values to count");
+            patches.push((*location, (range.start as i128 + (index % n) as i128) as Word));
+            index /= n;
+        }
+        patches.reverse();
+        Candidate { patches, input }
+    }
+
+    /// Runs a candidate until it stops or reaches `max_steps`, in which
+    /// case its state is still `CPUState::AwaitingInstruction`
+    pub fn run(&self, candidate: &Candidate) -> Computer {
+        let mut computer = candidate.computer(&self.tape);
+        while computer.steps() < self.max_steps && computer.step() == CPUState::AwaitingInstruction {}
+        computer
+    }
+
+    /// The first candidate, in index order, whose run passes the predicate
+    pub fn find<F>(&self, predicate: F) -> Option<Candidate>
+        where F: Fn(&Computer) -> bool + Sync
+    {
+        self.find_with(|candidate| predicate(&self.run(candidate)))
+    }
+
+    /// Like `find`, but the predicate evaluates the candidate itself, for
+    /// searches which need more than one run such as chained amplifiers
+    pub fn find_with<F>(&self, predicate: F) -> Option<Candidate>
+        where F: Fn(&Candidate) -> bool + Sync
+    {
+        (0 .. self.len()).into_par_iter()
+            .map(|index| self.candidate(index))
+            .find_first(|candidate| predicate(candidate))
+    }
+
+    /// The candidate whose run scores highest, ignoring those the objective
+    /// gives `None`. Ties go to the lowest index. If `until` is given the
+    /// search stops early once any candidate scores at least that much,
+    /// and returns the best found so far.
+    pub fn best<K, F>(&self, objective: F, until: Option<K>) -> Option<(Candidate, K)>
+        where K: Ord + Send + Sync, F: Fn(&Computer) -> Option<K> + Sync
+    {
+        self.best_with(|candidate| objective(&self.run(candidate)), until)
+    }
+
+    /// Like `best`, but the objective evaluates the candidate itself
+    pub fn best_with<K, F>(&self, objective: F, until: Option<K>) -> Option<(Candidate, K)>
+        where K: Ord + Send + Sync, F: Fn(&Candidate) -> Option<K> + Sync
+    {
+        let stop = AtomicBool::new(false);
+        (0 .. self.len()).into_par_iter()
+            .filter_map(|index| {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let candidate = self.candidate(index);
+                let score = objective(&candidate)?;
+                if until.as_ref().is_some_and(|until| score >= *until) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                Some((index, candidate, score))
+            })
+            .max_by(|a, b| a.2.cmp(&b.2).then(b.0.cmp(&a.0)))
+            .map(|(_, candidate, score)| (candidate, score))
+    }
+}
+
+/// The number of values in a range, or `None` if there are more than fit
+/// in a `usize`
+fn width(range: &Range<Word>) -> Option<usize> {
+    usize::try_from((range.end as i128 - range.start as i128).max(0)).ok()
+}
+
+/// Adds every ordering of `values[start ..]`, in lexicographic order if
+/// the values are sorted
+fn permute(values: &mut Vec<Word>, start: usize, into: &mut Vec<Vec<Word>>) {
+    if start == values.len() {
+        into.push(values.clone());
+        return;
+    }
+    for i in start .. values.len() {
+        values[start ..= i].rotate_right(1);
+        permute(values, start + 1, into);
+        values[start ..= i].rotate_left(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day_2() -> Tape {
+        Tape::load("../day-02/input.txt").unwrap()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut space = Space::new(&"99".parse().unwrap());
+        assert_eq!(1, space.len());
+        assert_eq!(Candidate::default(), space.candidate(0));
+
+        space.add_patch(1, 0 .. 2);
+        space.add_patch(2, 5 .. 8);
+        space.add_input(vec![1]);
+        space.add_input(vec![2]);
+        assert_eq!(12, space.len());
+        assert_eq!(Candidate { patches: vec![(1, 0), (2, 5)], input: vec![1] }, space.candidate(0));
+        assert_eq!(Candidate { patches: vec![(1, 0), (2, 5)], input: vec![2] }, space.candidate(1));
+        assert_eq!(Candidate { patches: vec![(1, 0), (2, 6)], input: vec![1] }, space.candidate(2));
+        assert_eq!(Candidate { patches: vec![(1, 1), (2, 7)], input: vec![2] }, space.candidate(11));
+
+        space.add_patch(3, 4 .. 4);
+        assert!(space.is_empty());
+    }
+
+    #[test]
+    fn test_wide_ranges() {
+        let mut space = Space::new(&"99".parse().unwrap());
+        space.add_patch(0, Word::MIN .. Word::MAX);
+        assert_eq!(Some(u64::MAX as usize), space.checked_len());
+        assert_eq!(vec![(0, Word::MIN + 1)], space.candidate(1).patches);
+        assert_eq!(vec![(0, Word::MAX - 1)], space.candidate(space.len() - 1).patches);
+
+        space.add_patch(1, 0 .. 2);
+        assert_eq!(None, space.checked_len());
+        assert!(!space.is_empty());
+
+        space.add_patch(2, 0 .. 0);
+        assert_eq!(Some(0), space.checked_len());
+    }
+
+    #[test]
+    fn test_permutations() {
+        let mut space = Space::new(&"99".parse().unwrap());
+        space.add_permutations(&[1, 2, 3]);
+        assert_eq!(vec![
+            vec![1, 2, 3], vec![1, 3, 2], vec![2, 1, 3], vec![2, 3, 1], vec![3, 1, 2], vec![3, 2, 1],
+        ], space.inputs);
+    }
+
+    #[test]
+    fn test_find_day_2() {
+        let mut space = Space::new(&day_2());
+        space.add_patch(1, 0 .. 100);
+        space.add_patch(2, 0 .. 100);
+
+        let found = space.find(|c| c.cpu_state() == CPUState::Halted && c.memory.read_direct(0) == 19690720);
+        assert_eq!(Some(vec![(1, 69), (2, 79)]), found.map(|c| c.patches));
+        assert_eq!(None, space.find(|c| c.memory.read_direct(0) == -1));
+    }
+
+    #[test]
+    fn test_best() {
+        let mut space = Space::new(&day_2());
+        space.add_patch(1, 0 .. 10);
+        space.add_patch(2, 0 .. 10);
+        let halted = |c: &Computer| Some(c.memory.read_direct(0)).filter(|_| c.cpu_state() == CPUState::Halted);
+
+        let (best, score) = space.best(halted, None).unwrap();
+        assert_eq!(vec![(1, 9), (2, 9)], best.patches);
+        assert_eq!(space.run(&best).memory.read_direct(0), score);
+
+        // Stopping early returns a score no better than the full search
+        let (_, early) = space.best(halted, Some(0)).unwrap();
+        assert!(early <= score);
+
+        // Ties go to the first candidate
+        let (first, _) = space.best(|_| Some(0), None).unwrap();
+        assert_eq!(space.candidate(0), first);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut space = Space::new(&"1105,1,0".parse().unwrap());
+        space.max_steps = 10;
+        let computer = space.run(&space.candidate(0));
+        assert_eq!(CPUState::AwaitingInstruction, computer.cpu_state());
+        assert_eq!(10, computer.steps());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::computer::{Reference, Word};
+
+/// How a piece of self modifying code was spotted
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ModificationKind {
+    /// An instruction wrote to a location which had already been executed
+    WroteExecuted,
+    /// A location was executed after an instruction had written to it
+    ExecutedWritten,
+}
+
+/// A single instance of a program modifying its own instructions
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Modification {
+    pub kind: ModificationKind,
+    /// The address of the instruction which did the writing
+    pub writer: Reference,
+    /// The opcode of the instruction which did the writing
+    pub writer_opcode: Word,
+    /// The location which was modified
+    pub location: Reference,
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ModificationKind::WroteExecuted => write!(f, "instruction {} at {} wrote to {}, which had already been executed",
+                                                      self.writer_opcode, self.writer, self.location),
+            ModificationKind::ExecutedWritten => write!(f, "executed {}, which was written by instruction {} at {}",
+                                                        self.location, self.writer_opcode, self.writer),
+        }
+    }
+}
+
+/// Watches memory accesses for programs writing to their own
+/// instructions. Enable it with `Computer::enable_self_modification_detection`.
+///
+/// ```
+/// use common::computer::{Computer, CPUState, Fault};
+///
+/// let mut computer = Computer::new_with_tape(&"1002,4,3,4,33".parse().unwrap());
+/// computer.enable_self_modification_detection(true);
+/// computer.run();
+///
+/// assert_eq!(CPUState::Faulted(Fault::SelfModification { writer: 0, location: 4 }), computer.cpu_state());
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct Detector {
+    /// If true, self modification stops the computer with a fault
+    pub fault: bool,
+    /// Everything spotted so far, in the order it happened. Each writer
+    /// and location is only logged the first time.
+    pub modifications: Vec<Modification>,
+
+    executed: Vec<bool>,
+    writers: HashMap<Reference, (Reference, Word)>,
+    /// The writers and locations already logged
+    reported: HashSet<(Reference, Reference)>,
+}
+
+impl Detector {
+    pub fn new(fault: bool) -> Detector {
+        Detector { fault, modifications: Vec::new(), executed: Vec::new(), writers: HashMap::new(), reported: HashSet::new() }
+    }
+
+    /// Forgets which locations have been executed and written, ready
+    /// for a new program. The log of modifications is kept.
+    pub fn reset(&mut self) {
+        self.executed.clear();
+        self.writers.clear();
+        self.reported.clear();
+    }
+
+    pub(crate) fn executing(&mut self, location: Reference) -> Option<Modification> {
+        if location < 0 {
+            return None;
+        }
+        let index = location as usize;
+        if index >= self.executed.len() {
+            self.executed.resize(index + 1, false);
+        }
+        self.executed[index] = true;
+
+        let (writer, writer_opcode) = *self.writers.get(&location)?;
+        self.log(Modification { kind: ModificationKind::ExecutedWritten, writer, writer_opcode, location })
+    }
+
+    pub(crate) fn writing(&mut self, location: Reference, writer: Reference, writer_opcode: Word) -> Option<Modification> {
+        self.writers.insert(location, (writer, writer_opcode));
+
+        if location < 0 || !self.executed.get(location as usize).cloned().unwrap_or(false) {
+            return None;
+        }
+        self.log(Modification { kind: ModificationKind::WroteExecuted, writer, writer_opcode, location })
+    }
+
+    fn log(&mut self, modification: Modification) -> Option<Modification> {
+        if !self.reported.insert((modification.writer, modification.location)) {
+            return None;
+        }
+        self.modifications.push(modification);
+        Some(modification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer::{Computer, CPUState, Fault};
+    use super::*;
+
+    #[test]
+    fn test_logs_without_faulting() {
+        let mut computer = Computer::new_with_tape(&"1002,4,3,4,33".parse().unwrap());
+        computer.enable_self_modification_detection(false);
+        computer.run();
+
+        assert_eq!(CPUState::Halted, computer.cpu_state());
+        let detector = computer.memory.self_modification.as_ref().unwrap();
+        assert_eq!(vec![
+            Modification { kind: ModificationKind::ExecutedWritten, writer: 0, writer_opcode: 1002, location: 4 },
+        ], detector.modifications);
+        assert_eq!("executed 4, which was written by instruction 1002 at 0", detector.modifications[0].to_string());
+    }
+
+    #[test]
+    fn test_write_to_executed() {
+        // Adds 1 to its own opcode after running it
+        let mut computer = Computer::new_with_tape(&"1001,0,1,0,99".parse().unwrap());
+        computer.enable_self_modification_detection(false);
+        computer.run();
+
+        assert_eq!(1002, computer.memory.read_direct(0));
+        let detector = computer.memory.self_modification.as_ref().unwrap();
+        assert_eq!(1, detector.modifications.len());
+        assert_eq!(ModificationKind::WroteExecuted, detector.modifications[0].kind);
+    }
+
+    #[test]
+    fn test_fault_prevents_write() {
+        let mut computer = Computer::new_with_tape(&"1001,0,1,0,99".parse().unwrap());
+        computer.enable_self_modification_detection(true);
+        computer.run();
+
+        assert_eq!(CPUState::Faulted(Fault::SelfModification { writer: 0, location: 0 }), computer.cpu_state());
+        assert_eq!(1001, computer.memory.read_direct(0));
+    }
+
+    #[test]
+    fn test_logs_each_modification_once() {
+        // Rewrites its own operand with the same value, then jumps back to do
+        // it again forever
+        let mut computer = Computer::new_with_tape(&"1101,3,0,3,1105,1,0".parse().unwrap());
+        computer.enable_self_modification_detection(false);
+        for _ in 0 .. 100 {
+            computer.step();
+        }
+
+        let detector = computer.memory.self_modification.as_ref().unwrap();
+        assert_eq!(vec![
+            Modification { kind: ModificationKind::WroteExecuted, writer: 0, writer_opcode: 1101, location: 3 },
+        ], detector.modifications);
+    }
+
+    #[test]
+    fn test_fault_prevents_output() {
+        // Patches the operand of the output instruction before running it
+        let mut computer = Computer::new_with_tape(&"1101,7,0,5,4,0,99".parse().unwrap());
+        computer.enable_self_modification_detection(true);
+        computer.run();
+
+        assert_eq!(CPUState::Faulted(Fault::SelfModification { writer: 0, location: 5 }), computer.cpu_state());
+        assert!(computer.io.output.is_empty());
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let mut computer = Computer::new_with_tape(&tape);
+        computer.enable_self_modification_detection(true);
+        computer.io.add_input(8);
+        computer.run();
+
+        assert_eq!(CPUState::Halted, computer.cpu_state());
+        assert!(computer.memory.self_modification.as_ref().unwrap().modifications.is_empty());
+    }
+}
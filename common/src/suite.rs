@@ -0,0 +1,160 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::computer::{Computer, Tape};
+use crate::tape_file::{Case, TapeError, TapeFile};
+
+/// Runs a case against a fresh computer, describing the first way the
+/// run differs from what the case expects
+pub fn check(tape: &Tape, case: &Case) -> Result<(), String> {
+    let mut computer = Computer::new_with_tape(tape);
+    for value in &case.input {
+        computer.io.add_input(*value);
+    }
+    computer.run();
+
+    let state = computer.cpu_state();
+    if !case.state.matches(state) {
+        return Err(format!("expected {:?}, but the computer was {:?}", case.state, state));
+    }
+    if computer.io.output != case.output {
+        return Err(format!("expected output {:?}, got {:?}", case.output, computer.io.output));
+    }
+    for (address, expected) in &case.memory {
+        let actual = computer.memory.read_direct(*address);
+        if actual != *expected {
+            return Err(format!("expected {} at {}, got {}", expected, address, actual));
+        }
+    }
+    Ok(())
+}
+
+/// A case which didn't do what its tape file expected
+#[derive(PartialEq, Clone, Debug)]
+pub struct Failure {
+    pub path: PathBuf,
+    /// The index of the case in its file, or `None` if the file couldn't
+    /// be loaded or has no cases
+    pub case: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.case {
+            Some(case) => write!(f, "{} case {}: {}", self.path.display(), case + 1, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// The results of running every case in a directory
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Report {
+    pub files: usize,
+    pub cases: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Checks every case in a tape file. A file with no cases counts as
+    /// a failure, since it can't be checking anything.
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let fail = |case, message| Failure { path: path.to_path_buf(), case, message };
+        self.files += 1;
+
+        let file = match TapeFile::load(path) {
+            Ok(file) => file,
+            Err(error) => return self.failures.push(fail(None, error.to_string())),
+        };
+        if file.cases.is_empty() {
+            return self.failures.push(fail(None, "no cases".to_string()));
+        }
+
+        let tape = file.tape();
+        for (i, case) in file.cases.iter().enumerate() {
+            self.cases += 1;
+            if let Err(message) = check(&tape, case) {
+                self.failures.push(fail(Some(i), message));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "{}", failure)?;
+        }
+        writeln!(f, "{} cases in {} files, {} failed", self.cases, self.files, self.failures.len())
+    }
+}
+
+/// Runs every `.tape` file in a directory, in name order. Each file is a
+/// tape file whose `case`, `memory` and `state` headers say what the tape
+/// should do.
+pub fn run_directory<P: AsRef<Path>>(path: P) -> Result<Report, TapeError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "tape") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut report = Report::default();
+    for path in paths {
+        report.run_file(path);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conformance_cases() {
+        let report = run_directory("tests/cases").unwrap();
+        assert!(report.passed(), "{}", report);
+        assert!(report.cases > 0);
+    }
+
+    #[test]
+    fn test_failures() {
+        let file: TapeFile = "\
+# case: 8 -> 1
+# case: 7 -> 1
+# case: 8 -> 1
+# memory: 9 = 7
+# case: 8 -> 1
+# state: awaiting input
+3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let results: Vec<Result<(), String>> = file.cases.iter().map(|c| check(&file.tape(), c)).collect();
+
+        assert_eq!(vec![
+            Ok(()),
+            Err("expected output [1], got [0]".to_string()),
+            Err("expected 7 at 9, got 1".to_string()),
+            Err("expected AwaitingInput, but the computer was Halted".to_string()),
+        ], results);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        report.run_file("no-such-file.tape");
+        report.run_file("../day-02/input.txt");
+
+        assert_eq!(2, report.files);
+        assert_eq!(0, report.cases);
+        assert_eq!(Some("../day-02/input.txt: no cases"), report.to_string().lines().nth(1));
+    }
+}
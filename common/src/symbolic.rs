@@ -0,0 +1,669 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::computer::{Computer, CPUState, DecodedInstruction, OpCode, ParameterMode, Reference, Tape, Word};
+use crate::instruction_set::InstructionSet;
+
+/// A value built up while executing symbolically
+#[derive(PartialEq, Clone, Debug)]
+pub enum Expr {
+    Const(Word),
+    Var(String),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equal(Rc<Expr>, Rc<Expr>),
+    /// A read from a location that depends on symbolic values, which
+    /// can't be evaluated without re-running the program
+    Load(Rc<Expr>),
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<Word> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn add(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_add(y))),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ => Rc::new(Expr::Add(a, b)),
+        }
+    }
+
+    fn mul(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_mul(y))),
+            (Some(0), _) | (_, Some(0)) => Rc::new(Expr::Const(0)),
+            (Some(1), _) => b,
+            (_, Some(1)) => a,
+            _ => Rc::new(Expr::Mul(a, b)),
+        }
+    }
+
+    fn less_than(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const((x < y) as Word)),
+            _ => Rc::new(Expr::LessThan(a, b)),
+        }
+    }
+
+    fn equal(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const((x == y) as Word)),
+            _ => Rc::new(Expr::Equal(a, b)),
+        }
+    }
+
+    /// Evaluates the expression with the passed variable values. Returns
+    /// `None` for unknown variables, loads and arithmetic overflow.
+    pub fn eval(&self, variables: &HashMap<String, Word>) -> Option<Word> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Var(name) => variables.get(name).cloned(),
+            Expr::Add(a, b) => a.eval(variables)?.checked_add(b.eval(variables)?),
+            Expr::Mul(a, b) => a.eval(variables)?.checked_mul(b.eval(variables)?),
+            Expr::LessThan(a, b) => Some((a.eval(variables)? < b.eval(variables)?) as Word),
+            Expr::Equal(a, b) => Some((a.eval(variables)? == b.eval(variables)?) as Word),
+            Expr::Load(_) => None,
+        }
+    }
+
+    /// Rewrites the expression as a linear combination of variables, if
+    /// it is one
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear { coefficients: HashMap::new(), constant: *value }),
+            Expr::Var(name) => {
+                let mut coefficients = HashMap::new();
+                coefficients.insert(name.clone(), 1);
+                Some(Linear { coefficients, constant: 0 })
+            },
+            Expr::Add(a, b) => a.linear()?.add(&b.linear()?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                if a.coefficients.is_empty() {
+                    b.scale(a.constant)
+                } else if b.coefficients.is_empty() {
+                    a.scale(b.constant)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equal(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+/// A sum of variables multiplied by constants, plus a constant
+#[derive(PartialEq, Clone, Debug)]
+pub struct Linear {
+    pub coefficients: HashMap<String, Word>,
+    pub constant: Word,
+}
+
+impl Linear {
+    fn add(mut self, other: &Linear) -> Option<Linear> {
+        for (name, coefficient) in &other.coefficients {
+            let entry = self.coefficients.entry(name.clone()).or_insert(0);
+            *entry = entry.checked_add(*coefficient)?;
+        }
+        self.constant = self.constant.checked_add(other.constant)?;
+        Some(self)
+    }
+
+    fn scale(mut self, factor: Word) -> Option<Linear> {
+        for coefficient in self.coefficients.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+        self.constant = self.constant.checked_mul(factor)?;
+        Some(self)
+    }
+
+    pub fn coefficient(&self, name: &str) -> Word {
+        self.coefficients.get(name).cloned().unwrap_or(0)
+    }
+}
+
+/// Reasons symbolic execution can't carry on
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum SymbolicError {
+    /// A jump depends on a symbolic value
+    SymbolicBranch { address: Reference },
+    /// A write goes to a location that depends on a symbolic value
+    SymbolicWrite { address: Reference },
+    /// An opcode, or the relative base, depends on a symbolic value
+    SymbolicInstruction { address: Reference },
+    InvalidInstruction { address: Reference, instruction: Word },
+    /// The program wanted more input than it was given
+    AwaitingInput { address: Reference },
+    /// The program didn't halt within the step limit
+    StepLimit,
+}
+
+/// An Intcode computer where memory and inputs hold expressions rather
+/// than numbers, supporting the standard instruction set.
+///
+/// ```
+/// use common::computer::Tape;
+/// use common::symbolic::SymbolicComputer;
+///
+/// let tape: Tape = "1101,0,0,0,1002,0,3,0,99".parse().unwrap();
+/// let mut computer = SymbolicComputer::new(&tape);
+/// computer.set_variable(1, "a");
+/// computer.set_variable(2, "b");
+/// computer.run(1000).unwrap();
+///
+/// assert_eq!("((a + b) * 3)", computer.read(0).to_string());
+/// ```
+pub struct SymbolicComputer {
+    memory: Vec<Rc<Expr>>,
+    instruction_pointer: Reference,
+    relative_base: Reference,
+    instruction_set: InstructionSet,
+    input: VecDeque<Rc<Expr>>,
+    pub output: Vec<Rc<Expr>>,
+}
+
+impl SymbolicComputer {
+    pub fn new(tape: &Tape) -> SymbolicComputer {
+        SymbolicComputer {
+            memory: tape.contents.iter().map(|w| Rc::new(Expr::Const(*w))).collect(),
+            instruction_pointer: 0,
+            relative_base: 0,
+            instruction_set: InstructionSet::intcode(),
+            input: VecDeque::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Replaces the contents of a memory location with a variable
+    pub fn set_variable(&mut self, location: Reference, name: &str) {
+        self.write(location, Rc::new(Expr::Var(name.to_string())));
+    }
+
+    pub fn add_input(&mut self, value: Expr) {
+        self.input.push_back(Rc::new(value));
+    }
+
+    pub fn read(&self, location: Reference) -> Rc<Expr> {
+        match self.memory.get(location as usize) {
+            Some(value) if location >= 0 => value.clone(),
+            _ => Rc::new(Expr::Const(0)),
+        }
+    }
+
+    fn write(&mut self, location: Reference, value: Rc<Expr>) {
+        assert!(location >= 0);
+        if location as usize >= self.memory.len() {
+            self.memory.resize(location as usize + 1, Rc::new(Expr::Const(0)));
+        }
+        self.memory[location as usize] = value;
+    }
+
+    /// Runs until the program halts, giving up after `max_steps`
+    /// instructions or when a symbolic value gets in the way
+    pub fn run(&mut self, max_steps: usize) -> Result<(), SymbolicError> {
+        for _ in 0 .. max_steps {
+            if !self.step()? {
+                return Ok(());
+            }
+        }
+        Err(SymbolicError::StepLimit)
+    }
+
+    /// Executes one instruction, returning false once halted
+    fn step(&mut self) -> Result<bool, SymbolicError> {
+        let address = self.instruction_pointer;
+        let word = self.read(address).constant()
+            .ok_or(SymbolicError::SymbolicInstruction { address })?;
+        let op = DecodedInstruction::from_word(word, &self.instruction_set)
+            .ok_or(SymbolicError::InvalidInstruction { address, instruction: word })?;
+        let opcode = OpCode::from_word(op.instruction.opcode)
+            .ok_or(SymbolicError::InvalidInstruction { address, instruction: word })?;
+
+        let parameter = |i: usize| (address + 1 + i as Reference, op.modes.mode(i));
+        self.instruction_pointer = address + op.len() as Reference;
+
+        match opcode {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equal => {
+                let a = self.read_parameter(parameter(0));
+                let b = self.read_parameter(parameter(1));
+                let result = match opcode {
+                    OpCode::Add => Expr::add(a, b),
+                    OpCode::Mul => Expr::mul(a, b),
+                    OpCode::LessThan => Expr::less_than(a, b),
+                    _ => Expr::equal(a, b),
+                };
+                self.write_parameter(parameter(2), result, address)?;
+            },
+            OpCode::ConsumeInput => {
+                let value = self.input.pop_front().ok_or(SymbolicError::AwaitingInput { address })?;
+                self.write_parameter(parameter(0), value, address)?;
+            },
+            OpCode::ProduceOutput => {
+                let value = self.read_parameter(parameter(0));
+                self.output.push(value);
+            },
+            OpCode::JumpIfNotZero | OpCode::JumpIfZero => {
+                let condition = self.read_parameter(parameter(0)).constant()
+                    .ok_or(SymbolicError::SymbolicBranch { address })?;
+                if (condition != 0) == (opcode == OpCode::JumpIfNotZero) {
+                    self.instruction_pointer = self.read_parameter(parameter(1)).constant()
+                        .ok_or(SymbolicError::SymbolicBranch { address })?;
+                }
+            },
+            OpCode::AdjustRelativeBase => {
+                self.relative_base += self.read_parameter(parameter(0)).constant()
+                    .ok_or(SymbolicError::SymbolicInstruction { address })?;
+            },
+            OpCode::Halt => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Works out the location a parameter refers to, or the expression
+    /// for it if it depends on a symbolic value
+    fn resolve(&self, (location, mode): (Reference, ParameterMode)) -> Result<Reference, Rc<Expr>> {
+        let value = self.read(location);
+        match mode {
+            ParameterMode::Immediate => Ok(location),
+            ParameterMode::Position => value.constant().ok_or(value),
+            ParameterMode::Relative => match value.constant() {
+                Some(offset) => Ok(self.relative_base + offset),
+                None => Err(Expr::add(value, Rc::new(Expr::Const(self.relative_base)))),
+            },
+        }
+    }
+
+    fn read_parameter(&self, parameter: (Reference, ParameterMode)) -> Rc<Expr> {
+        match self.resolve(parameter) {
+            Ok(location) => self.read(location),
+            Err(address) => Rc::new(Expr::Load(address)),
+        }
+    }
+
+    fn write_parameter(&mut self, parameter: (Reference, ParameterMode), value: Rc<Expr>, address: Reference) -> Result<(), SymbolicError> {
+        let location = self.resolve(parameter).map_err(|_| SymbolicError::SymbolicWrite { address })?;
+        self.write(location, value);
+        Ok(())
+    }
+}
+
+/// Where the value of a variable goes
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Source {
+    /// Patched into memory before the program runs
+    Memory(Reference),
+    /// Supplied as the nth input
+    Input(usize),
+}
+
+/// The value a solution must produce
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Target {
+    /// The value left in a memory location after halting
+    Memory(Reference),
+    /// The nth value output
+    Output(usize),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Variable {
+    pub name: String,
+    pub source: Source,
+    /// The values the variable may take
+    pub range: Range<Word>,
+}
+
+/// How a `Problem` was solved
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Method {
+    /// The target was a linear formula of the variables
+    Linear,
+    /// The target was a formula, evaluated for each combination
+    Formula,
+    /// The program was run for each combination
+    Search,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Solution {
+    /// The value of each variable, in the order they were added
+    pub values: Vec<Word>,
+    pub method: Method,
+}
+
+/// A question of the form "which values of these variables make the
+/// program produce this value?".
+///
+/// The program is executed symbolically first. If the target comes out
+/// as a linear formula it is solved directly, otherwise the formula is
+/// evaluated or the program run over every combination of values.
+///
+/// ```
+/// use common::symbolic::{Method, Problem, Target};
+///
+/// // Multiplies the two inputs and outputs 3 more than the result
+/// let tape = "3,15,3,16,2,15,16,17,101,3,17,17,4,17,99".parse().unwrap();
+/// let mut problem = Problem::new(&tape);
+/// problem.add_input_variable("x", 0 .. 10);
+/// problem.add_input_variable("y", 0 .. 10);
+///
+/// let solution = problem.solve(Target::Output(0), 45).unwrap();
+/// assert_eq!(vec![7, 6], solution.values);
+/// assert_eq!(Method::Formula, solution.method);
+/// ```
+pub struct Problem {
+    pub tape: Tape,
+    pub variables: Vec<Variable>,
+    /// The most instructions to execute for each run
+    pub max_steps: usize,
+    inputs: usize,
+}
+
+impl Problem {
+    pub fn new(tape: &Tape) -> Problem {
+        Problem { tape: Tape { contents: tape.contents.clone() }, variables: Vec::new(), max_steps: 1_000_000, inputs: 0 }
+    }
+
+    /// Adds a variable whose value is written to memory before running
+    pub fn add_memory_variable(&mut self, name: &str, location: Reference, range: Range<Word>) {
+        self.variables.push(Variable { name: name.to_string(), source: Source::Memory(location), range });
+    }
+
+    /// Adds a variable supplied as the next input
+    pub fn add_input_variable(&mut self, name: &str, range: Range<Word>) {
+        self.variables.push(Variable { name: name.to_string(), source: Source::Input(self.inputs), range });
+        self.inputs += 1;
+    }
+
+    /// Finds values for the variables which produce `value` at the target
+    pub fn solve(&self, target: Target, value: Word) -> Option<Solution> {
+        match self.formula(target) {
+            Some(formula) => match formula.linear() {
+                Some(linear) => self.solve_linear(&linear, value)
+                    .map(|values| Solution { values, method: Method::Linear }),
+                None => self.solve_formula(&formula, value),
+            },
+            None => self.search(target, value)
+                .map(|values| Solution { values, method: Method::Search }),
+        }
+    }
+
+    /// Executes the program symbolically, returning the formula for the
+    /// target if it could be worked out
+    pub fn formula(&self, target: Target) -> Option<Rc<Expr>> {
+        let mut computer = SymbolicComputer::new(&self.tape);
+        for variable in &self.variables {
+            match variable.source {
+                Source::Memory(location) => computer.set_variable(location, &variable.name),
+                Source::Input(_) => computer.add_input(Expr::Var(variable.name.clone())),
+            }
+        }
+
+        computer.run(self.max_steps).ok()?;
+
+        let result = match target {
+            Target::Memory(location) => computer.read(location),
+            Target::Output(index) => computer.output.get(index)?.clone(),
+        };
+        if contains_load(&result) {
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Solves a linear formula by trying every value of all but one
+    /// variable and calculating the last one
+    fn solve_linear(&self, linear: &Linear, value: Word) -> Option<Vec<Word>> {
+        let solved = self.variables.iter().rposition(|v| linear.coefficient(&v.name) != 0);
+        let solved = match solved {
+            Some(solved) => solved,
+            None => return self.first_combination().filter(|_| linear.constant == value),
+        };
+        let coefficient = linear.coefficient(&self.variables[solved].name);
+
+        let mut found = None;
+        self.for_each_combination(solved, |values| {
+            // Everything but the variable being solved for
+            let mut values = values.to_vec();
+            values[solved] = 0;
+            let rest = self.evaluate(linear, &values);
+            let remainder = match rest.and_then(|r| value.checked_sub(r)) {
+                Some(remainder) => remainder,
+                None => return false,
+            };
+            let quotient = match remainder.checked_rem(coefficient) {
+                Some(0) => remainder.checked_div(coefficient),
+                _ => None,
+            };
+            match quotient {
+                Some(quotient) if self.variables[solved].range.contains(&quotient) => values[solved] = quotient,
+                _ => return false,
+            }
+
+            if self.evaluate(linear, &values) != Some(value) {
+                return false;
+            }
+            found = Some(values);
+            true
+        });
+        found
+    }
+
+    /// The value of a linear formula with the variables set to `values`,
+    /// or `None` if it overflows
+    fn evaluate(&self, linear: &Linear, values: &[Word]) -> Option<Word> {
+        values.iter().zip(&self.variables).try_fold(linear.constant, |total, (v, variable)| {
+            total.checked_add(v.checked_mul(linear.coefficient(&variable.name))?)
+        })
+    }
+
+    fn solve_formula(&self, formula: &Expr, value: Word) -> Option<Solution> {
+        let mut found = None;
+        self.for_each_combination(self.variables.len(), |values| {
+            let variables = self.variables.iter().map(|v| v.name.clone()).zip(values.iter().cloned()).collect();
+            if formula.eval(&variables) == Some(value) {
+                found = Some(values.to_vec());
+                return true;
+            }
+            false
+        });
+        found.map(|values| Solution { values, method: Method::Formula })
+    }
+
+    /// Runs the program for every combination of values
+    fn search(&self, target: Target, value: Word) -> Option<Vec<Word>> {
+        let mut computer = Computer::new();
+        let mut found = None;
+        self.for_each_combination(self.variables.len(), |values| {
+            computer.reset_and_load_tape(&self.tape);
+            for (variable, v) in self.variables.iter().zip(values) {
+                match variable.source {
+                    Source::Memory(location) => computer.memory.write_direct(location, *v),
+                    Source::Input(_) => computer.io.add_input(*v),
+                }
+            }
+
+            let mut steps = 0;
+            while computer.step() == CPUState::AwaitingInstruction && steps < self.max_steps {
+                steps += 1;
+            }
+
+            let result = match target {
+                Target::Memory(location) => Some(computer.memory.read_direct(location)),
+                Target::Output(index) => computer.io.output.get(index).cloned(),
+            };
+            if computer.cpu_state() == CPUState::Halted && result == Some(value) {
+                found = Some(values.to_vec());
+                return true;
+            }
+            false
+        });
+        found
+    }
+
+    fn first_combination(&self) -> Option<Vec<Word>> {
+        if self.variables.iter().any(|v| v.range.start >= v.range.end) {
+            return None;
+        }
+        Some(self.variables.iter().map(|v| v.range.start).collect())
+    }
+
+    /// Calls `f` with every combination of variable values, except for
+    /// the variable at `skip` which is left at its first value, until
+    /// `f` returns true
+    fn for_each_combination<F>(&self, skip: usize, mut f: F)
+        where F: FnMut(&[Word]) -> bool
+    {
+        let mut values = match self.first_combination() {
+            Some(values) => values,
+            None => return,
+        };
+
+        loop {
+            if f(&values) {
+                return;
+            }
+
+            // Count up like an odometer, ignoring the skipped variable
+            let mut index = 0;
+            loop {
+                if index == values.len() {
+                    return;
+                }
+                if index != skip {
+                    values[index] += 1;
+                    if values[index] < self.variables[index].range.end {
+                        break;
+                    }
+                    values[index] = self.variables[index].range.start;
+                }
+                index += 1;
+            }
+        }
+    }
+}
+
+fn contains_load(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(_) | Expr::Var(_) => false,
+        Expr::Add(a, b) | Expr::Mul(a, b) | Expr::LessThan(a, b) | Expr::Equal(a, b) => contains_load(a) || contains_load(b),
+        Expr::Load(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplification() {
+        let tape: Tape = "1101,0,0,13,1002,13,1,14,1002,13,0,15,99".parse().unwrap();
+        let mut computer = SymbolicComputer::new(&tape);
+        computer.set_variable(2, "x");
+        computer.run(100).unwrap();
+
+        assert_eq!(Expr::Var("x".to_string()), *computer.read(13));
+        assert_eq!(Expr::Var("x".to_string()), *computer.read(14));
+        assert_eq!(Expr::Const(0), *computer.read(15));
+    }
+
+    #[test]
+    fn test_symbolic_branch() {
+        let tape: Tape = "3,0,1005,0,6,99,104,1,99".parse().unwrap();
+        let mut computer = SymbolicComputer::new(&tape);
+        computer.add_input(Expr::Var("x".to_string()));
+        assert_eq!(Err(SymbolicError::SymbolicBranch { address: 2 }), computer.run(100));
+    }
+
+    #[test]
+    fn test_linear() {
+        let tape: Tape = "1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap();
+        let mut problem = Problem::new(&tape);
+        problem.add_memory_variable("a", 9, 0 .. 100);
+        problem.add_memory_variable("b", 10, 0 .. 100);
+
+        let formula = problem.formula(Target::Memory(0)).unwrap();
+        let linear = formula.linear().unwrap();
+        assert_eq!(50, linear.coefficient("a"));
+        assert_eq!(50, linear.coefficient("b"));
+        assert_eq!(0, linear.constant);
+
+        let solution = problem.solve(Target::Memory(0), 3500).unwrap();
+        assert_eq!(Method::Linear, solution.method);
+        assert_eq!(3500, 50 * solution.values[0] + 50 * solution.values[1]);
+
+        assert_eq!(None, problem.solve(Target::Memory(0), 3501));
+    }
+
+    #[test]
+    fn test_linear_ranges_not_from_zero() {
+        let tape: Tape = "1,5,6,0,99,0,0".parse().unwrap();
+        let mut problem = Problem::new(&tape);
+        problem.add_memory_variable("a", 5, 0 .. 10);
+        problem.add_memory_variable("b", 6, 5 .. 10);
+
+        let solution = problem.solve(Target::Memory(0), 12).unwrap();
+        assert_eq!(Method::Linear, solution.method);
+        assert_eq!(vec![3, 9], solution.values);
+
+        assert_eq!(None, problem.solve(Target::Memory(0), 4));
+    }
+
+    #[test]
+    fn test_linear_division_overflow() {
+        // Negates x, which can't reach Word::MIN from inside the range
+        let tape: Tape = "1002,5,-1,0,99,0".parse().unwrap();
+        let mut problem = Problem::new(&tape);
+        problem.add_memory_variable("x", 5, Word::MIN .. 1);
+
+        assert_eq!(None, problem.solve(Target::Memory(0), Word::MIN));
+    }
+
+    #[test]
+    fn test_falls_back_to_search() {
+        // Outputs 1 if the input is 8, branching on it
+        let tape: Tape = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1".parse().unwrap();
+        let mut problem = Problem::new(&tape);
+        problem.add_input_variable("x", -10 .. 10);
+
+        let solution = problem.solve(Target::Output(0), 0).unwrap();
+        assert_eq!(Method::Search, solution.method);
+        assert_eq!(vec![0], solution.values);
+    }
+
+    #[test]
+    fn test_day_02() {
+        let tape: Tape = std::fs::read_to_string("../day-02/input.txt").unwrap().parse().unwrap();
+        let mut problem = Problem::new(&tape);
+        problem.add_memory_variable("noun", 1, 0 .. 100);
+        problem.add_memory_variable("verb", 2, 0 .. 100);
+
+        let solution = problem.solve(Target::Memory(0), 19690720).unwrap();
+        assert_eq!(Method::Linear, solution.method);
+        assert_eq!(vec![69, 79], solution.values);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::binary::DecodeError;
+use crate::computer::{CPUState, Reference, Tape, Word};
+
+/// How a run is expected to end
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ExpectedState {
+    Halted,
+    AwaitingInput,
+    /// With any fault
+    Faulted,
+}
+
+impl ExpectedState {
+    pub fn matches(self, state: CPUState) -> bool {
+        matches!((self, state),
+            (ExpectedState::Halted, CPUState::Halted)
+            | (ExpectedState::AwaitingInput, CPUState::AwaitingInput)
+            | (ExpectedState::Faulted, CPUState::Faulted(_)))
+    }
+}
+
+/// An input and what a tape is expected to do with it
+#[derive(PartialEq, Clone, Debug)]
+pub struct Case {
+    pub input: Vec<Word>,
+    pub output: Vec<Word>,
+    /// Locations and the values they should hold once the run ends
+    pub memory: Vec<(Reference, Word)>,
+    pub state: ExpectedState,
+}
+
+/// Words to be placed in memory starting at an address
+#[derive(PartialEq, Clone, Debug)]
+pub struct Segment {
+    pub start: Reference,
+    pub words: Vec<Word>,
+}
+
+/// A tape along with everything known about it.
+///
+/// Tape files are the usual comma separated words with optional header
+/// lines starting with `#`. Recognised headers are `# key: value`:
+///
+/// - `name: text` names the tape
+/// - `case: 1,2 -> 3,4` gives the output expected from an input, and can
+///   be repeated
+/// - `memory: address = value, ...` adds words expected in memory at the
+///   end of the previous case, where addresses can be symbols
+/// - `state: halted`, `awaiting input` or `faulted` gives how the previous
+///   case should end, which is halted if not given
+/// - `symbol: name = address` labels an address
+/// - `patch: address = value` changes a word when the tape is loaded,
+///   where the address can be a number or a symbol
+///
+/// Any other `#` lines are comments. The words can be split over several
+/// lines, and a line `@address` starts a new segment so the words which
+/// follow it are placed from that address. A bare tape is a valid tape
+/// file with no headers and a single segment at 0.
+///
+/// ```
+/// use common::tape_file::TapeFile;
+///
+/// let file: TapeFile = "\
+/// ## name: Day 2 example
+/// ## symbol: noun = 1
+/// ## patch: noun = 5
+/// 1,0,0,0,99
+/// @8
+/// 7
+/// ".parse().unwrap();
+///
+/// assert_eq!(Some("Day 2 example".to_string()), file.name);
+/// assert_eq!(vec![1, 5, 0, 0, 99, 0, 0, 0, 7], file.tape().contents);
+/// ```
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct TapeFile {
+    pub name: Option<String>,
+    pub cases: Vec<Case>,
+    pub symbols: BTreeMap<String, Reference>,
+    /// Applied in order by `tape()`
+    pub patches: Vec<(Reference, Word)>,
+    pub segments: Vec<Segment>,
+}
+
+/// Why a tape file couldn't be loaded
+#[derive(Debug)]
+pub enum TapeError {
+    Io(io::Error),
+    /// Line `line` (from 1) couldn't be understood
+    Parse { line: usize, message: String },
+    /// A binary tape couldn't be decoded
+    Binary(DecodeError),
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeError::Io(error) => write!(f, "{}", error),
+            TapeError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            TapeError::Binary(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<io::Error> for TapeError {
+    fn from(error: io::Error) -> Self {
+        TapeError::Io(error)
+    }
+}
+
+impl TapeFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TapeFile, TapeError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// The address of a symbol
+    pub fn address(&self, symbol: &str) -> Option<Reference> {
+        self.symbols.get(symbol).cloned()
+    }
+
+    /// Builds the tape from the segments, filling any gaps with 0, then
+    /// applies the patches
+    pub fn tape(&self) -> Tape {
+        let mut contents = Vec::new();
+        let mut put = |address: Reference, value: Word| {
+            if address as usize >= contents.len() {
+                contents.resize(address as usize + 1, 0);
+            }
+            contents[address as usize] = value;
+        };
+
+        for segment in &self.segments {
+            for (i, word) in segment.words.iter().enumerate() {
+                put(segment.start + i as Reference, *word);
+            }
+        }
+        for (address, value) in &self.patches {
+            put(*address, *value);
+        }
+        Tape { contents }
+    }
+}
+
+fn words(s: &str) -> Result<Vec<Word>, String> {
+    s.split(',')
+        .map(|w| w.trim())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.parse().map_err(|_| format!("{} isn't a number", w)))
+        .collect()
+}
+
+fn address(s: &str) -> Result<Reference, String> {
+    match s.trim().parse() {
+        Ok(address) if address >= 0 => Ok(address),
+        _ => Err(format!("{} isn't an address", s.trim())),
+    }
+}
+
+/// Splits `left = right`
+fn assignment(s: &str) -> Result<(&str, &str), String> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(left), Some(right)) => Ok((left.trim(), right.trim())),
+        _ => Err(format!("expected name = value, got {}", s)),
+    }
+}
+
+impl TapeFile {
+    /// An address given as a symbol or a number
+    fn location(&self, s: &str) -> Result<Reference, String> {
+        match self.address(s) {
+            Some(location) => Ok(location),
+            None => address(s),
+        }
+    }
+
+    fn last_case(&mut self) -> Result<&mut Case, String> {
+        self.cases.last_mut().ok_or_else(|| "expected a case first".to_string())
+    }
+
+    fn parse_header(&mut self, header: &str) -> Result<(), String> {
+        let mut parts = header.splitn(2, ':');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => return Ok(()),
+        };
+
+        match key {
+            "name" => self.name = Some(value.to_string()),
+            "case" => {
+                let mut sides = value.splitn(2, "->");
+                let input = words(sides.next().unwrap_or(""))?;
+                let output = words(sides.next().ok_or("expected input -> output")?)?;
+                self.cases.push(Case { input, output, memory: Vec::new(), state: ExpectedState::Halted });
+            },
+            "memory" => {
+                let mut cells = Vec::new();
+                for cell in value.split(',') {
+                    let (location, value) = assignment(cell)?;
+                    cells.push((self.location(location)?, value.parse().map_err(|_| format!("{} isn't a number", value))?));
+                }
+                self.last_case()?.memory.extend(cells);
+            },
+            "state" => {
+                let state = match value {
+                    "halted" => ExpectedState::Halted,
+                    "awaiting input" => ExpectedState::AwaitingInput,
+                    "faulted" => ExpectedState::Faulted,
+                    _ => return Err(format!("{} isn't a state", value)),
+                };
+                self.last_case()?.state = state;
+            },
+            "symbol" => {
+                let (name, value) = assignment(value)?;
+                self.symbols.insert(name.to_string(), address(value)?);
+            },
+            "patch" => {
+                let (location, value) = assignment(value)?;
+                let location = self.location(location)?;
+                let value = value.parse().map_err(|_| format!("{} isn't a number", value))?;
+                self.patches.push((location, value));
+            },
+            // Anything else is a comment
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TapeFile {
+    type Err = TapeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut file = TapeFile::default();
+        let mut segment = Segment { start: 0, words: Vec::new() };
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let result = if let Some(header) = line.strip_prefix('#') {
+                file.parse_header(header)
+            } else if let Some(start) = line.strip_prefix('@') {
+                address(start).map(|start| {
+                    let finished = std::mem::replace(&mut segment, Segment { start, words: Vec::new() });
+                    if !finished.words.is_empty() {
+                        file.segments.push(finished);
+                    }
+                })
+            } else {
+                words(line).map(|words| segment.words.extend(words))
+            };
+            result.map_err(|message| TapeError::Parse { line: i + 1, message })?;
+        }
+
+        if !segment.words.is_empty() {
+            file.segments.push(segment);
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_tape() {
+        let file: TapeFile = "1,9,10,3,\n2,3,11,0,99,30,40,50\n".parse().unwrap();
+        assert_eq!(TapeFile {
+            segments: vec![Segment { start: 0, words: vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50] }],
+            ..TapeFile::default()
+        }, file);
+    }
+
+    #[test]
+    fn test_headers() {
+        let file: TapeFile = "\
+# name: Compare to 8
+# Comments can go anywhere
+# case: 8 -> 1
+# case: 7 -> 0
+# memory: 9 = 0, 10 = 8
+# case: ->
+# state: awaiting input
+# symbol: input = 9
+# symbol: eight = 10
+# patch: eight = 7
+# patch: 10 = 9
+3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+
+        assert_eq!(Some("Compare to 8".to_string()), file.name);
+        assert_eq!(vec![
+            Case { input: vec![8], output: vec![1], memory: vec![], state: ExpectedState::Halted },
+            Case { input: vec![7], output: vec![0], memory: vec![(9, 0), (10, 8)], state: ExpectedState::Halted },
+            Case { input: vec![], output: vec![], memory: vec![], state: ExpectedState::AwaitingInput },
+        ], file.cases);
+        assert_eq!(Some(9), file.address("input"));
+        assert_eq!(vec![(10, 7), (10, 9)], file.patches);
+        assert_eq!(9, file.tape().contents[10]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |s: &str| match s.parse::<TapeFile>() {
+            Err(TapeError::Parse { line, message }) => format!("{}: {}", line, message),
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!("2: x isn't a number", error("1,2\n3,x"));
+        assert_eq!("1: nowhere isn't an address", error("# patch: nowhere = 1"));
+        assert_eq!("1: expected input -> output", error("# case: 1,2"));
+        assert_eq!("3: -1 isn't an address", error("1\n\n@-1"));
+        assert_eq!("1: expected a case first", error("# state: halted"));
+        assert_eq!("2: stopped isn't a state", error("# case: ->\n# state: stopped"));
+    }
+
+    #[test]
+    fn test_load_day_input() {
+        let tape = Tape::load("../day-02/input.txt").unwrap();
+        assert_eq!(Some(&1), tape.contents.first());
+        assert!(matches!(Tape::load("no-such-tape.txt"), Err(TapeError::Io(_))));
+    }
+}
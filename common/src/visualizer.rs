@@ -0,0 +1,392 @@
+use std::io;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+
+use crate::computer::{Computer, CPUState, Reference, Tape, Word};
+use crate::coverage::CellAccess;
+use crate::disassembler::disassemble_with_hints;
+
+/// The most instructions run per frame
+const MAX_SPEED: u64 = 1 << 20;
+
+/// How a range of memory has been used, for the heatmap
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Heat {
+    Untouched,
+    /// Touched before the last frame, `level` being roughly the log of
+    /// the number of accesses
+    Old { level: usize, written: bool },
+    /// Written during the last frame
+    Written,
+    /// Read or executed, but not written, during the last frame
+    Read,
+}
+
+/// A terminal UI for watching a computer run a tape, one instruction at
+/// a time or many per frame.
+///
+/// It shows the disassembly around the instruction pointer, the
+/// registers, the queued input and the output, and a heatmap of how
+/// memory has been used. Space plays and pauses, `s` steps, `+` and `-`
+/// change how many instructions run per frame, `i` types input, `r`
+/// starts again and `q` quits. Input is asked for whenever the program
+/// waits for some.
+pub struct Visualizer {
+    pub computer: Computer,
+    tape: Tape,
+    input: Vec<Word>,
+    pub playing: bool,
+    /// Instructions run per frame while playing
+    pub speed: u64,
+    /// The accesses at the start of the last frame, to pick out recent
+    /// ones
+    before: Vec<CellAccess>,
+    /// Input being typed, if any
+    pub entry: Option<String>,
+    pub quit: bool,
+}
+
+impl Visualizer {
+    /// Loads the tape, queueing the input
+    pub fn new(tape: &Tape, input: &[Word]) -> Visualizer {
+        let mut visualizer = Visualizer {
+            computer: Computer::new(),
+            tape: tape.clone(),
+            input: input.to_vec(),
+            playing: false,
+            speed: 1,
+            before: Vec::new(),
+            entry: None,
+            quit: false,
+        };
+        visualizer.reset();
+        visualizer
+    }
+
+    /// Reloads the tape and its input
+    pub fn reset(&mut self) {
+        self.computer = Computer::new_with_tape(&self.tape);
+        self.computer.enable_coverage();
+        for value in &self.input {
+            self.computer.io.add_input(*value);
+        }
+        self.playing = false;
+        self.entry = None;
+        self.snapshot();
+    }
+
+    fn snapshot(&mut self) {
+        let coverage = self.computer.memory.coverage.as_ref().unwrap();
+        self.before = (0 .. self.computer.memory.len()).map(|l| coverage.cell(l as Reference)).collect();
+    }
+
+    /// Runs up to `steps` instructions, stopping early if the computer
+    /// stops or wants input
+    fn run(&mut self, steps: u64) -> CPUState {
+        self.snapshot();
+        for _ in 0 .. steps {
+            match self.computer.step() {
+                CPUState::AwaitingInstruction => {},
+                state => {
+                    self.playing = false;
+                    if state == CPUState::AwaitingInput {
+                        self.entry = Some(String::new());
+                    }
+                    return state;
+                },
+            }
+        }
+        CPUState::AwaitingInstruction
+    }
+
+    /// Executes one instruction
+    pub fn step(&mut self) -> CPUState {
+        self.run(1)
+    }
+
+    /// Moves on by a frame, running `speed` instructions if playing
+    pub fn tick(&mut self) {
+        if self.playing {
+            self.run(self.speed);
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if let Some(entry) = self.entry.as_mut() {
+            match key.code {
+                KeyCode::Char(c) if c.is_ascii_digit() || (c == '-' && entry.is_empty()) => entry.push(c),
+                KeyCode::Backspace => {
+                    entry.pop();
+                },
+                KeyCode::Enter => {
+                    if let Ok(value) = entry.parse() {
+                        self.computer.io.add_input(value);
+                        self.entry = None;
+                    }
+                },
+                KeyCode::Esc => self.entry = None,
+                _ => {},
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char(' ') => self.playing = !self.playing,
+            KeyCode::Char('s') | KeyCode::Right => {
+                self.playing = false;
+                self.step();
+            },
+            KeyCode::Char('+') | KeyCode::Char('=') => self.speed = (self.speed * 2).min(MAX_SPEED),
+            KeyCode::Char('-') => self.speed = (self.speed / 2).max(1),
+            KeyCode::Char('i') => self.entry = Some(String::new()),
+            KeyCode::Char('r') => self.reset(),
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            _ => {},
+        }
+    }
+
+    /// How the locations in `range` have been used, taking the hottest
+    pub fn heat(&self, range: std::ops::Range<Reference>) -> Heat {
+        let coverage = self.computer.memory.coverage.as_ref().unwrap();
+        let mut heat = Heat::Untouched;
+
+        for location in range {
+            let now = coverage.cell(location);
+            let before = self.before.get(location as usize).cloned().unwrap_or_default();
+            let total = now.fetches + now.operand_reads + now.reads + now.writes;
+
+            let cell = if now.writes > before.writes {
+                Heat::Written
+            } else if total > before.fetches + before.operand_reads + before.reads + before.writes {
+                Heat::Read
+            } else if total > 0 {
+                Heat::Old { level: (usize::BITS - total.leading_zeros()) as usize, written: now.writes > 0 }
+            } else {
+                Heat::Untouched
+            };
+
+            heat = match (heat, cell) {
+                (Heat::Written, _) | (_, Heat::Written) => Heat::Written,
+                (Heat::Read, _) | (_, Heat::Read) => Heat::Read,
+                (Heat::Old { level: a, written: x }, Heat::Old { level: b, written: y }) => Heat::Old { level: a.max(b), written: x || y },
+                (Heat::Untouched, cell) => cell,
+                (heat, Heat::Untouched) => heat,
+            };
+        }
+        heat
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [main, heatmap, help] = Layout::vertical([Constraint::Min(8), Constraint::Length(10), Constraint::Length(1)]).areas(frame.area());
+        let [code, side] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+        let [registers, input, output] = Layout::vertical([Constraint::Length(7), Constraint::Length(3), Constraint::Min(3)]).areas(side);
+
+        self.draw_code(frame, code);
+        self.draw_registers(frame, registers);
+        self.draw_input(frame, input);
+        self.draw_output(frame, output);
+        self.draw_heatmap(frame, heatmap);
+
+        let keys = "space play/pause  s step  +/- speed  i input  r reset  q quit";
+        frame.render_widget(Paragraph::new(keys).style(Style::default().fg(Color::DarkGray)), help);
+    }
+
+    fn draw_code(&self, frame: &mut Frame, area: Rect) {
+        let ip = self.computer.instruction_pointer();
+        let coverage = self.computer.memory.coverage.as_ref().unwrap();
+        let lines = disassemble_with_hints(self.computer.memory.contents(), |a| a == ip || coverage.cell(a).fetches > 0);
+
+        let height = area.height.saturating_sub(2) as usize;
+        let current = lines.iter().position(|l| l.contains(ip)).unwrap_or(0);
+        let first = current.saturating_sub(height / 3).min(lines.len().saturating_sub(height));
+
+        let text: Vec<Line> = lines.iter().enumerate().skip(first).take(height)
+            .map(|(i, line)| {
+                if i == current {
+                    Line::styled(format!("> {}", line), Style::default().add_modifier(Modifier::REVERSED))
+                } else if coverage.cell(line.address).fetches > 0 {
+                    Line::raw(format!("  {}", line))
+                } else {
+                    Line::styled(format!("  {}", line), Style::default().fg(Color::DarkGray))
+                }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(" Code ")), area);
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect) {
+        let registers = self.computer.registers();
+        let status = if self.playing { "playing" } else { "paused" };
+        let text = vec![
+            Line::raw(format!("ip    {}", registers.instruction_pointer)),
+            Line::raw(format!("rb    {}", registers.relative_base)),
+            Line::raw(format!("steps {}", registers.steps)),
+            Line::raw(format!("state {:?}", registers.state)),
+            Line::raw(format!("{}, {} per frame", status, self.speed)),
+        ];
+        frame.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(" Registers ")), area);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let queued: Vec<String> = self.computer.io.pending_input().iter().map(|v| v.to_string()).collect();
+        let mut spans = vec![Span::raw(queued.join(","))];
+        if let Some(entry) = &self.entry {
+            spans.push(Span::styled(format!(" > {}_", entry), Style::default().fg(Color::Yellow)));
+        }
+        frame.render_widget(Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL).title(" Input ")), area);
+    }
+
+    fn draw_output(&self, frame: &mut Frame, area: Rect) {
+        let output = &self.computer.io.output;
+        let height = area.height.saturating_sub(2) as usize;
+        let text: Vec<Line> = output[output.len().saturating_sub(height) ..].iter().map(|v| Line::raw(v.to_string())).collect();
+        let title = format!(" Output ({}) ", output.len());
+        frame.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title)), area);
+    }
+
+    fn draw_heatmap(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+        let inner = block.inner(area);
+        let cells = (inner.width as usize * inner.height as usize).max(1);
+        let per_cell = self.computer.memory.len().div_ceil(cells).max(1) as Reference;
+        let ip = self.computer.instruction_pointer();
+
+        let text: Vec<Line> = (0 .. inner.height as Reference)
+            .map(|row| {
+                let spans: Vec<Span> = (0 .. inner.width as Reference)
+                    .map(|column| {
+                        let start = (row * inner.width as Reference + column) * per_cell;
+                        let range = start .. start + per_cell;
+                        if range.contains(&ip) {
+                            return Span::styled("@", Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
+                        }
+                        match self.heat(range) {
+                            Heat::Untouched => Span::styled("·", Style::default().fg(Color::DarkGray)),
+                            Heat::Written => Span::styled("█", Style::default().fg(Color::Red)),
+                            Heat::Read => Span::styled("█", Style::default().fg(Color::Cyan)),
+                            Heat::Old { level, written } => {
+                                let shade = ["░", "░", "▒", "▒", "▓"][level.min(5) - 1];
+                                Span::styled(shade, Style::default().fg(if written { Color::Magenta } else { Color::Blue }))
+                            },
+                        }
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+
+        let title = format!(" Memory, {} words per cell ", per_cell);
+        frame.render_widget(Paragraph::new(text).block(block.title(title)), area);
+    }
+}
+
+/// Runs the visualizer in the terminal until it is quit
+pub fn run(visualizer: &mut Visualizer) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut frames = || -> io::Result<()> {
+        while !visualizer.quit {
+            terminal.draw(|frame| visualizer.draw(frame))?;
+            if event::poll(Duration::from_millis(30))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        visualizer.handle_key(key);
+                    }
+                }
+            }
+            visualizer.tick();
+        }
+        Ok(())
+    };
+    let result = frames();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::KeyModifiers;
+    use ratatui::Terminal;
+
+    fn press(visualizer: &mut Visualizer, code: KeyCode) {
+        visualizer.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn screen(visualizer: &Visualizer) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+        terminal.draw(|frame| visualizer.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0 .. buffer.area.height)
+            .map(|y| (0 .. buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn test_controls() {
+        let tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let mut visualizer = Visualizer::new(&tape, &[]);
+
+        press(&mut visualizer, KeyCode::Char('+'));
+        press(&mut visualizer, KeyCode::Char('+'));
+        assert_eq!(4, visualizer.speed);
+
+        // Asks for input as soon as the program wants some
+        press(&mut visualizer, KeyCode::Char(' '));
+        visualizer.tick();
+        assert!(!visualizer.playing);
+        assert_eq!(Some(String::new()), visualizer.entry);
+
+        press(&mut visualizer, KeyCode::Char('8'));
+        press(&mut visualizer, KeyCode::Enter);
+        assert_eq!(vec![8], visualizer.computer.io.pending_input());
+
+        press(&mut visualizer, KeyCode::Char('s'));
+        assert_eq!(1, visualizer.computer.steps());
+        press(&mut visualizer, KeyCode::Char(' '));
+        visualizer.tick();
+        assert_eq!(vec![1], visualizer.computer.io.output);
+        assert_eq!(CPUState::Halted, visualizer.computer.cpu_state());
+
+        press(&mut visualizer, KeyCode::Char('r'));
+        assert_eq!(0, visualizer.computer.steps());
+        press(&mut visualizer, KeyCode::Char('q'));
+        assert!(visualizer.quit);
+    }
+
+    #[test]
+    fn test_heat() {
+        let tape = "1101,2,3,7,1001,7,1,7,99".parse().unwrap();
+        let mut visualizer = Visualizer::new(&tape, &[]);
+        assert_eq!(Heat::Untouched, visualizer.heat(0 .. 9));
+
+        visualizer.step();
+        assert_eq!(Heat::Written, visualizer.heat(7 .. 8));
+        assert_eq!(Heat::Read, visualizer.heat(0 .. 4));
+        assert_eq!(Heat::Untouched, visualizer.heat(4 .. 7));
+
+        visualizer.step();
+        assert_eq!(Heat::Old { level: 1, written: false }, visualizer.heat(0 .. 1));
+        assert_eq!(Heat::Written, visualizer.heat(0 .. 9));
+    }
+
+    #[test]
+    fn test_draw() {
+        let tape = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let mut visualizer = Visualizer::new(&tape, &[8, 5]);
+        visualizer.step();
+        let screen = screen(&visualizer);
+
+        assert!(screen.contains("> 00002  8,9,10,9"), "{}", screen);
+        assert!(screen.contains("ip    2"), "{}", screen);
+        assert!(screen.contains("steps 1"), "{}", screen);
+        assert!(screen.contains("│5"), "{}", screen);
+        assert!(screen.contains("Memory, 1 words per cell"), "{}", screen);
+    }
+}
@@ -1,5 +1,6 @@
 use common::Puzzle;
 use common::computer::{Computer, Tape, Word};
+use common::symbolic::{Problem, Target};
 
 fn main() {
     let mut a: Puzzle1 =  Default::default();
@@ -45,16 +46,13 @@ impl Puzzle for Puzzle2 {
     type ParsedLine = Tape;
 
     fn process_item(&mut self, item: Self::ParsedLine) {
-        for noun in 0 .. 100 {
-            for verb in 0 .. 100 {
-                let result = Puzzle1::run_tape(noun, verb, &item);
-                if result == 19690720 {
-                    self.noun = noun;
-                    self.verb = verb;
-                    return;
-                }
-            }
-        }
+        let mut problem = Problem::new(&item);
+        problem.add_memory_variable("noun", 1, 0 .. 100);
+        problem.add_memory_variable("verb", 2, 0 .. 100);
+
+        let solution = problem.solve(Target::Memory(0), 19690720).expect("No solution");
+        self.noun = solution.values[0];
+        self.verb = solution.values[1];
     }
 
     fn final_result(&mut self) -> String {
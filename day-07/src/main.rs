@@ -1,6 +1,7 @@
 use permutohedron::heap_recursive;
 use common::Puzzle;
 use common::computer::{Computer, Tape, CPUState, Word};
+use common::search::Space;
 
 fn main() {
     let mut a = Puzzle1 { result: 0 };
@@ -18,56 +19,21 @@ impl Puzzle for Puzzle1 {
     type ParsedLine = Tape;
 
     fn process_item(&mut self, item: Self::ParsedLine) {
-        let mut amp_a = Computer::new();
-        let mut amp_b = Computer::new();
-        let mut amp_c = Computer::new();
-        let mut amp_d = Computer::new();
-        let mut amp_e = Computer::new();
-
-        let mut inputs = vec![0,1,2,3,4];
-        let mut permutations = Vec::new();
-        heap_recursive(&mut inputs, |permutation| {
-            permutations.push(permutation.to_vec())
-        });
-
-        let mut max = 0;
-        for input in permutations {
-            // Set the phase settings
-            amp_a.reset_and_load_tape(&item);
-            amp_a.io.add_input(input[0]);
-
-            amp_b.reset_and_load_tape(&item);
-            amp_b.io.add_input(input[1]);
-
-            amp_c.reset_and_load_tape(&item);
-            amp_c.io.add_input(input[2]);
-
-            amp_d.reset_and_load_tape(&item);
-            amp_d.io.add_input(input[3]);
-
-            amp_e.reset_and_load_tape(&item);
-            amp_e.io.add_input(input[4]);
-
-            // Run the first amp
-            amp_a.io.add_input(0);
-            amp_a.run();
-
-            // Chain the remaining ones
-            amp_b.io.add_input(*amp_a.io.output.first().unwrap());
-            amp_b.run();
-
-            amp_c.io.add_input(*amp_b.io.output.first().unwrap());
-            amp_c.run();
-
-            amp_d.io.add_input(*amp_c.io.output.first().unwrap());
-            amp_d.run();
-
-            amp_e.io.add_input(*amp_d.io.output.first().unwrap());
-            amp_e.run();
-
-            // Check the output
-            max = max.max(*amp_e.io.output.first().unwrap());
-        }
+        let mut space = Space::new(&item);
+        space.add_permutations(&[0,1,2,3,4]);
+
+        // Each candidate's input is the phase settings, one per amp
+        let (_, max) = space.best_with(|candidate| {
+            let mut signal = 0;
+            for phase in &candidate.input {
+                let mut amp = Computer::new_with_tape(&space.tape);
+                amp.io.add_input(*phase);
+                amp.io.add_input(signal);
+                amp.run();
+                signal = *amp.io.output.first()?;
+            }
+            Some(signal)
+        }, None).expect("No phase settings");
         self.result = max;
     }
 